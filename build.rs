@@ -0,0 +1,29 @@
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    // sqlx::migrate!() embeds the migrations at compile time, rebuild when one is added
+    println!("cargo:rerun-if-changed=migrations");
+
+    // reported by GET /version
+    let git_sha = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|sha| sha.trim().to_owned())
+        .unwrap_or_else(|| "unknown".to_owned());
+    println!("cargo:rustc-env=GIT_SHA={}", git_sha);
+
+    let built_at = SystemTime::now().duration_since(UNIX_EPOCH).expect("clock is before 1970").as_secs();
+    println!("cargo:rustc-env=BUILT_AT={}", built_at);
+
+    // a new commit or changed sources make for a new sha and build time, a source tarball without .git has neither
+    println!("cargo:rerun-if-changed=src");
+    if Path::new(".git").exists() {
+        println!("cargo:rerun-if-changed=.git/HEAD");
+        println!("cargo:rerun-if-changed=.git/refs");
+    }
+}
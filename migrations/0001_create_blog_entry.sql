@@ -1,13 +1,18 @@
-drop table if exists blog_entry;
 create table blog_entry
 (
+    id      bigserial primary key,
     created timestamptz,
+    updated timestamptz not null default now(),
     title   varchar(100),
     author  varchar(40),
     text    text
 );
 
+-- full-text search over title and text, the expression must be kept in sync with search_blogs
+create index blog_entry_search on blog_entry
+    using gin (to_tsvector('english', coalesce(title, '') || ' ' || coalesce(text, '')));
+
 insert into blog_entry(created, title, author, text)
 values (now(), 'Get enterprisey with Rust', 'Sander', 'Lorem Ipsum');
 insert into blog_entry(created, title, author, text)
-values (now(), 'Get whimsical with data', 'Sander', 'Lorem Ipsum');
\ No newline at end of file
+values (now(), 'Get whimsical with data', 'Sander', 'Lorem Ipsum');
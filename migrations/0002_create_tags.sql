@@ -0,0 +1,15 @@
+create table tags
+(
+    id   bigserial primary key,
+    name varchar(40) not null unique
+);
+
+create table blog_entry_tags
+(
+    blog_entry_id bigint not null references blog_entry (id) on delete cascade,
+    tag_id        bigint not null references tags (id) on delete cascade,
+    primary key (blog_entry_id, tag_id)
+);
+
+-- the primary key covers lookups by entry, this one the filter on a tag
+create index blog_entry_tags_tag_id on blog_entry_tags (tag_id);
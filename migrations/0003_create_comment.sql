@@ -0,0 +1,10 @@
+create table comment
+(
+    id       bigserial primary key,
+    entry_id bigint      not null references blog_entry (id) on delete cascade,
+    author   varchar(40) not null,
+    body     text        not null,
+    created  timestamptz not null default now()
+);
+
+create index comment_entry_id on comment (entry_id, created);
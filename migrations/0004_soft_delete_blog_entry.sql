@@ -0,0 +1,3 @@
+-- deleted entries keep their row and are hidden from reads, see delete_blog
+alter table blog_entry
+    add column deleted_at timestamptz;
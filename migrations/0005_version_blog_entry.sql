@@ -0,0 +1,3 @@
+-- bumped on every update, lets writers detect concurrent changes, see update_blog
+alter table blog_entry
+    add column version integer not null default 1;
@@ -0,0 +1,44 @@
+-- random ids don't give away how many entries there are, nor which ids to try
+alter table blog_entry
+    add column uuid uuid not null default gen_random_uuid();
+
+alter table blog_entry_tags
+    add column blog_entry_uuid uuid;
+update blog_entry_tags bt
+set blog_entry_uuid = e.uuid
+from blog_entry e
+where e.id = bt.blog_entry_id;
+
+alter table comment
+    add column entry_uuid uuid;
+update comment c
+set entry_uuid = e.uuid
+from blog_entry e
+where e.id = c.entry_id;
+
+-- dropping the old columns takes their keys and indexes with them
+alter table blog_entry_tags
+    drop column blog_entry_id;
+alter table comment
+    drop column entry_id;
+alter table blog_entry
+    drop column id;
+
+alter table blog_entry
+    rename column uuid to id;
+alter table blog_entry
+    add primary key (id);
+
+alter table blog_entry_tags
+    rename column blog_entry_uuid to blog_entry_id;
+alter table blog_entry_tags
+    alter column blog_entry_id set not null,
+    add foreign key (blog_entry_id) references blog_entry (id) on delete cascade,
+    add primary key (blog_entry_id, tag_id);
+
+alter table comment
+    rename column entry_uuid to entry_id;
+alter table comment
+    alter column entry_id set not null,
+    add foreign key (entry_id) references blog_entry (id) on delete cascade;
+create index comment_entry_id on comment (entry_id, created);
@@ -0,0 +1,10 @@
+-- remembers which entry a POST with an Idempotency-Key created, so a retry gets the same entry, see add_blog
+create table idempotency_key (
+    author text not null,
+    key text not null,
+    -- the normalized entry that was sent, a retry has to send the same one
+    request text not null,
+    entry_id uuid not null references blog_entry (id) on delete cascade,
+    created timestamptz not null default now(),
+    primary key (author, key)
+);
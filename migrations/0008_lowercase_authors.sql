@@ -0,0 +1,3 @@
+-- authors are stored in lowercase from now on, so one author isn't listed under several spellings
+update blog_entry set author = lower(author) where author <> lower(author);
+update comment set author = lower(author) where author <> lower(author);
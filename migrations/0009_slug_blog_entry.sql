@@ -0,0 +1,21 @@
+-- the friendly name of an entry in urls, derived from the title by slugify when the entry is created;
+-- entries inserted in bulk have none
+alter table blog_entry
+    add column slug text;
+
+-- existing entries get roughly what slugify makes of their title, the oldest one keeping it when titles repeat
+with bases as (
+    select id, created,
+           coalesce(nullif(trim(both '-' from left(regexp_replace(lower(title), '[^[:alnum:]]+', '-', 'g'), 80)), ''), 'entry') as slug
+    from blog_entry
+), slugs as (
+    select id, slug, row_number() over (partition by slug order by created, id) as n
+    from bases
+)
+update blog_entry
+set slug = case when slugs.n = 1 then slugs.slug else slugs.slug || '-' || slugs.n end
+from slugs
+where slugs.id = blog_entry.id;
+
+alter table blog_entry
+    add constraint blog_entry_slug_key unique (slug);
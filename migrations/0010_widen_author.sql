@@ -0,0 +1,3 @@
+-- authors are the email addresses of the signed in users, which can be up to 254 characters
+alter table blog_entry alter column author type varchar(254);
+alter table comment alter column author type varchar(254);
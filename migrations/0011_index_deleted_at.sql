@@ -0,0 +1,2 @@
+-- the entry list asks for the latest deletion on every request, see get_blogs
+create index blog_entry_deleted_at on blog_entry (deleted_at);
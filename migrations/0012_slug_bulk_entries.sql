@@ -0,0 +1,21 @@
+-- every entry gets a slug when it is created, the ones inserted in bulk included; those inserted in bulk
+-- before that get theirs now, made like unique_slug makes them
+do $$
+declare
+    entry record;
+    base text;
+    candidate text;
+    counter integer;
+begin
+    for entry in select id, title from blog_entry where slug is null order by created, id loop
+        base := coalesce(nullif(trim(both '-' from left(regexp_replace(lower(entry.title), '[^[:alnum:]]+', '-', 'g'), 80)), ''), 'entry');
+        candidate := base;
+        counter := 2;
+        while exists (select 1 from blog_entry where slug = candidate) loop
+            candidate := base || '-' || counter;
+            counter := counter + 1;
+        end loop;
+        update blog_entry set slug = candidate where id = entry.id;
+    end loop;
+end
+$$;
@@ -0,0 +1,269 @@
+//! Errors the handlers return, and how they end up in the response.
+
+use std::{collections::BTreeMap, time::Duration};
+use axum::{http::{header, HeaderValue, StatusCode}, Json, response::{IntoResponse, Response}};
+use axum::extract::rejection::{JsonRejection, QueryRejection};
+use serde::Serialize;
+use thiserror::Error;
+use tracing::error;
+
+use crate::config::redact_passwords;
+use crate::middleware::REQUEST_ID;
+
+/// Utility function for mapping any error into a `500 Internal Server Error` response. The error itself is
+/// only logged, it may tell more about the database than a client should know.
+/// Queries cancelled by the statement timeout become a `504 Gateway Timeout` instead, and waiting
+/// in vain for a pooled connection a `503 Service Unavailable` to be retried after `POOL_TIMEOUT_RETRY_AFTER`.
+pub fn internal_error<E>(err: E) -> ApiError
+    where
+        E: std::error::Error + 'static,
+{
+    if is_query_canceled(&err) {
+        return ApiError::new(StatusCode::GATEWAY_TIMEOUT, "database query timed out");
+    }
+    if matches!((&err as &dyn std::error::Error).downcast_ref::<sqlx::Error>(), Some(sqlx::Error::PoolTimedOut)) {
+        return ApiError::new(StatusCode::SERVICE_UNAVAILABLE, "no database connection available, try again later")
+            .with_retry_after(POOL_TIMEOUT_RETRY_AFTER);
+    }
+    error!("internal error: {}", redact_passwords(&err.to_string()));
+    ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "internal server error")
+}
+
+/// When a client may retry after all pooled connections were busy, they are usually given back within moments.
+const POOL_TIMEOUT_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+/// SQLSTATE Postgres reports when a query is cancelled, e.g. because it ran past `statement_timeout`.
+const QUERY_CANCELED: &str = "57014";
+
+fn is_query_canceled(err: &(dyn std::error::Error + 'static)) -> bool {
+    err.downcast_ref::<sqlx::Error>()
+        .and_then(sqlx::Error::as_database_error)
+        .is_some_and(|db_err| db_err.code().as_deref() == Some(QUERY_CANCELED))
+}
+
+/// SQLSTATE Postgres reports when an insert or update violates a unique constraint.
+const UNIQUE_VIOLATION: &str = "23505";
+
+/// Maps database errors on writes to a response, turning unique constraint violations into
+/// `409 Conflict` and everything else into what `internal_error` makes of it.
+pub fn map_db_error(err: sqlx::Error) -> ApiError {
+    match err.as_database_error() {
+        Some(db_err) if db_err.code().as_deref() == Some(UNIQUE_VIOLATION) => {
+            let message = match db_err.constraint() {
+                Some(constraint) => format!("blog entry conflicts with an existing entry ({})", constraint),
+                None => "blog entry conflicts with an existing entry".to_owned(),
+            };
+            ApiError::new(StatusCode::CONFLICT, message)
+        }
+        _ => internal_error(err),
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ServerError {
+    #[error(transparent)]
+    ValidationError(#[from] validator::ValidationErrors),
+
+    #[error(transparent)]
+    AxumFormRejection(#[from] JsonRejection),
+
+    #[error(transparent)]
+    AxumQueryRejection(#[from] QueryRejection),
+
+    #[error("missing bearer token")]
+    MissingToken,
+
+    #[error("invalid bearer token: {0}")]
+    InvalidToken(#[from] jsonwebtoken::errors::Error),
+
+    #[error("authentication is not configured, set JWT_SECRET")]
+    AuthNotConfigured,
+
+    #[error("this requires an admin token")]
+    NotAdmin,
+}
+
+impl IntoResponse for ServerError {
+    fn into_response(self) -> Response {
+        ApiError::from(self).into_response()
+    }
+}
+
+impl From<ServerError> for ApiError {
+    fn from(err: ServerError) -> Self {
+        match err {
+            ServerError::ValidationError(errors) => {
+                ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, "input validation error").with_details(field_errors(&errors))
+            }
+            ServerError::AxumFormRejection(rejection) if exceeds_body_limit(&rejection) => ApiError::payload_too_large(),
+            ServerError::AxumFormRejection(rejection) => ApiError::bad_request(json_rejection_message(&rejection)),
+            ServerError::AxumQueryRejection(rejection) => ApiError::bad_request(rejection.to_string()),
+            ServerError::MissingToken | ServerError::InvalidToken(_) | ServerError::AuthNotConfigured => {
+                ApiError::new(StatusCode::UNAUTHORIZED, err.to_string())
+            }
+            ServerError::NotAdmin => ApiError::new(StatusCode::FORBIDDEN, err.to_string()),
+        }
+    }
+}
+
+/// Whether reading the body failed because it grew past the `RequestBodyLimitLayer` limit.
+fn exceeds_body_limit(rejection: &JsonRejection) -> bool {
+    std::iter::successors(Some(rejection as &(dyn std::error::Error + 'static)), |err| err.source())
+        .any(|err| err.is::<http_body::LengthLimitError>())
+}
+
+/// Tells apart the ways a JSON body can be unusable, axum names them but says little more.
+fn json_rejection_message(rejection: &JsonRejection) -> String {
+    let serde_error = std::iter::successors(Some(rejection as &(dyn std::error::Error + 'static)), |err| err.source())
+        .find_map(|err| err.downcast_ref::<serde_json::Error>());
+    match (rejection, serde_error) {
+        (JsonRejection::MissingJsonContentType(_), _) => "expected a JSON body with Content-Type: application/json".to_owned(),
+        // nothing at all to parse is reported at the very start
+        (JsonRejection::JsonSyntaxError(_), Some(err)) if err.is_eof() && err.line() == 1 && err.column() == 0 => {
+            "the request body is empty, expected a JSON object".to_owned()
+        }
+        (JsonRejection::JsonSyntaxError(_), Some(err)) => format!("the request body is not valid JSON: {}", err),
+        (JsonRejection::JsonDataError(_), Some(err)) => format!("the JSON body does not match the expected fields: {}", err),
+        _ => rejection.to_string(),
+    }
+}
+
+/// Field name to the messages of every validation rule it failed.
+pub type FieldErrors = BTreeMap<String, Vec<String>>;
+
+/// Error returned by the handlers, rendered as `{ "error": ..., "status": ..., "details": {...} }`.
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    message: String,
+    details: FieldErrors,
+    /// The requested path, for errors about the path itself.
+    path: Option<String>,
+    /// Sent as `Retry-After`, for errors that go away by themselves.
+    retry_after: Option<Duration>,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        ApiError { status, message: message.into(), details: FieldErrors::new(), path: None, retry_after: None }
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        ApiError::new(StatusCode::BAD_REQUEST, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        ApiError::new(StatusCode::NOT_FOUND, message)
+    }
+
+    pub fn payload_too_large() -> Self {
+        ApiError::new(StatusCode::PAYLOAD_TOO_LARGE, "request body is too large")
+    }
+
+    pub fn with_details(mut self, details: FieldErrors) -> Self {
+        self.details = details;
+        self
+    }
+
+    pub fn with_path(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    pub fn with_retry_after(mut self, retry_after: Duration) -> Self {
+        self.retry_after = Some(retry_after);
+        self
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let request_id = REQUEST_ID.try_with(Clone::clone).ok();
+        let body = ApiErrorBody {
+            error: &self.message,
+            status: self.status.as_u16(),
+            details: &self.details,
+            path: self.path.as_deref(),
+            request_id: request_id.as_deref(),
+        };
+        let mut response = (self.status, Json(body)).into_response();
+        if let Some(retry_after) = self.retry_after {
+            response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(retry_after.as_secs()));
+        }
+        response
+    }
+}
+
+#[derive(Serialize)]
+struct ApiErrorBody<'a> {
+    error: &'a str,
+    status: u16,
+    details: &'a FieldErrors,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<&'a str>,
+}
+
+/// Collects the messages of all failed rules per field, e.g. `{"title": ["Title length must be between 10 and 100"]}`.
+/// Errors in nested structs and lists are keyed by their path, e.g. `entries[2].title`.
+fn field_errors(errors: &validator::ValidationErrors) -> FieldErrors {
+    let mut fields = FieldErrors::new();
+    collect_field_errors(errors, "", &mut fields);
+    fields
+}
+
+fn collect_field_errors(errors: &validator::ValidationErrors, prefix: &str, fields: &mut FieldErrors) {
+    for (field, kind) in errors.errors() {
+        let path = format!("{}{}", prefix, field);
+        match kind {
+            validator::ValidationErrorsKind::Field(errors) => {
+                let messages = errors
+                    .iter()
+                    .map(|error| error.message.as_ref().unwrap_or(&error.code).to_string())
+                    .collect();
+                fields.insert(path, messages);
+            }
+            validator::ValidationErrorsKind::Struct(errors) => collect_field_errors(errors, &format!("{}.", path), fields),
+            validator::ValidationErrorsKind::List(errors) => {
+                for (index, errors) in errors {
+                    collect_field_errors(errors, &format!("{}[{}].", path, index), fields);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDb;
+
+    #[tokio::test]
+    async fn answers_queries_past_the_statement_timeout_with_a_504() {
+        let db = TestDb::new().await;
+        let mut tx = db.pool.begin().await.unwrap();
+        sqlx::query("set local statement_timeout = 10").execute(&mut tx).await.unwrap();
+        let err = sqlx::query("select pg_sleep(1)").execute(&mut tx).await.unwrap_err();
+
+        assert_eq!(internal_error(err).status, StatusCode::GATEWAY_TIMEOUT);
+    }
+
+    #[test]
+    fn answers_pool_timeouts_with_a_503_to_retry() {
+        let response = internal_error(sqlx::Error::PoolTimedOut).into_response();
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+    }
+
+    #[tokio::test]
+    async fn keeps_database_errors_out_of_the_response() {
+        let db = TestDb::new().await;
+        let err = sqlx::query("select * from no_such_table").execute(&db.pool).await.unwrap_err();
+        let err = internal_error(err);
+
+        assert_eq!(err.status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(err.message, "internal server error");
+    }
+}
@@ -0,0 +1,125 @@
+//! Extractors that validate the request before a handler gets to see it.
+
+use axum::{http::header, BoxError};
+use axum::extract::{FromRequest, Query, RequestParts, Json as ExtractJson};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use validator::Validate;
+use async_trait::async_trait;
+
+use crate::error::ServerError;
+use crate::models::normalize_email;
+use crate::state::AppState;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ValidatedJson<T>(pub T);
+
+#[async_trait]
+impl<T, B> FromRequest<B> for ValidatedJson<T>
+    where
+        T: DeserializeOwned + Normalize + Validate,
+        B: http_body::Body + Send,
+        B::Data: Send,
+        B::Error: Into<BoxError>,
+{
+    type Rejection = ServerError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let ExtractJson(mut value) = ExtractJson::<T>::from_request(req).await?;
+        value.normalize();
+        if req.extensions().get::<AppState>().is_some_and(|state| state.config.sanitize_html) {
+            value.sanitize_html();
+        }
+        value.validate()?;
+        Ok(ValidatedJson(value))
+    }
+}
+
+/// Query parameters that passed validation, like `ValidatedJson` does for bodies.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ValidatedQuery<T>(pub T);
+
+#[async_trait]
+impl<T, B> FromRequest<B> for ValidatedQuery<T>
+    where
+        T: DeserializeOwned + Validate,
+        B: Send,
+{
+    type Rejection = ServerError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let Query(value) = Query::<T>::from_request(req).await?;
+        value.validate()?;
+        Ok(ValidatedQuery(value))
+    }
+}
+
+/// Cleans up user input before it is validated, so that e.g. padding doesn't count towards a minimum length.
+pub trait Normalize {
+    fn normalize(&mut self);
+
+    /// Strips unsafe markup from the fields that may be rendered as HTML, when `SANITIZE_HTML` is set.
+    fn sanitize_html(&mut self) {}
+}
+
+/// Claims expected in the bearer tokens, `exp` is checked when decoding.
+#[derive(Debug, Deserialize)]
+struct Claims {
+    sub: String,
+    #[serde(default)]
+    admin: bool,
+}
+
+/// The caller authenticated by a valid `Authorization: Bearer <jwt>` header, signed with `JWT_SECRET`.
+#[derive(Debug, Clone)]
+pub struct AuthUser {
+    pub sub: String,
+    /// Set by an `"admin": true` claim.
+    pub admin: bool,
+}
+
+#[async_trait]
+impl<B> FromRequest<B> for AuthUser
+    where
+        B: Send,
+{
+    type Rejection = ServerError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let key = req
+            .extensions()
+            .get::<DecodingKey>()
+            .ok_or(ServerError::AuthNotConfigured)?;
+        let token = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or(ServerError::MissingToken)?;
+
+        let data = jsonwebtoken::decode::<Claims>(token, key, &Validation::new(Algorithm::HS256))?;
+        // the subject is the email the user's entries and comments are stored under
+        Ok(AuthUser { sub: normalize_email(&data.claims.sub), admin: data.claims.admin })
+    }
+}
+
+/// A signed in user with an `"admin": true` claim, anyone else is turned away with a `403`.
+#[derive(Debug, Clone)]
+pub struct AdminUser(pub AuthUser);
+
+#[async_trait]
+impl<B> FromRequest<B> for AdminUser
+    where
+        B: Send,
+{
+    type Rejection = ServerError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let user = AuthUser::from_request(req).await?;
+        if !user.admin {
+            return Err(ServerError::NotAdmin);
+        }
+        Ok(AdminUser(user))
+    }
+}
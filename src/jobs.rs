@@ -0,0 +1,58 @@
+//! Background work running next to the server.
+
+use std::time::{Duration, Instant};
+use sqlx::postgres::PgPool;
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+/// Every `interval`, removes the entries that were soft-deleted longer than `retention` ago, along with their comments and tags.
+/// Returns once `shutdown` fires.
+pub async fn prune_deleted_entries(pool: PgPool, retention: Duration, interval: Duration, mut shutdown: watch::Receiver<Option<Instant>>) {
+    let mut ticks = tokio::time::interval(interval);
+    loop {
+        tokio::select! {
+            _ = ticks.tick() => {}
+            _ = shutdown.changed() => return,
+        }
+
+        match sqlx::query("delete from blog_entry where deleted_at < now() - make_interval(secs => $1)")
+            .bind(retention.as_secs_f64())
+            .execute(&pool)
+            .await
+        {
+            Ok(result) => info!("pruned {} entries deleted more than {:?} ago", result.rows_affected(), retention),
+            Err(err) => warn!("pruning deleted entries failed, trying again in {:?}: {}", interval, err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDb;
+
+    #[tokio::test]
+    async fn prunes_entries_deleted_before_the_retention_period() {
+        let db = TestDb::new().await;
+        let insert = |deleted_at: &'static str| {
+            sqlx::query("insert into blog_entry (created, title, author, text, deleted_at) values (now(), 'Pruned', 'prune@example.com', 'Lorem ipsum', now() - $1::interval)")
+                .bind(deleted_at)
+                .execute(&db.pool)
+        };
+        insert("31 days").await.unwrap();
+        insert("1 day").await.unwrap();
+
+        let (stop, shutdown) = watch::channel(None);
+        let task = tokio::spawn(prune_deleted_entries(db.pool.clone(), Duration::from_secs(30 * 24 * 60 * 60), Duration::from_secs(3600), shutdown));
+        // the first tick fires right away
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        stop.send(Some(Instant::now())).unwrap();
+        task.await.unwrap();
+
+        let (left,): (i64,) = sqlx::query_as("select count(*) from blog_entry where author = 'prune@example.com'")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(left, 1);
+    }
+}
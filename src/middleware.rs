@@ -0,0 +1,252 @@
+//! Middleware wrapped around the routes: metrics, rate limiting, authentication, request ids and security headers.
+
+use std::{collections::HashMap, net::SocketAddr, sync::Arc, time::{Duration, Instant}};
+use axum::{http::{header::{self, HeaderName}, HeaderValue, Method, Request, StatusCode}, middleware::Next, response::{IntoResponse, Response}};
+use axum::extract::ConnectInfo;
+use tracing::{info_span, Instrument};
+use uuid::Uuid;
+
+use crate::error::ApiError;
+
+/// Middleware recording the count, errors and latency of requests per method and status.
+pub async fn track_metrics<B>(req: Request<B>, next: Next<B>) -> Response {
+    let start = Instant::now();
+    let method = req.method().to_string();
+
+    let response = next.run(req).await;
+
+    let status = response.status();
+    let labels = [("method", method), ("status", status.as_u16().to_string())];
+    metrics::counter!("http_requests_total", &labels).increment(1);
+    metrics::histogram!("http_request_duration_seconds", &labels).record(start.elapsed().as_secs_f64());
+    if status.is_client_error() || status.is_server_error() {
+        metrics::counter!("http_request_errors_total", &labels).increment(1);
+    }
+    response
+}
+
+/// Middleware rejecting requests whose `Content-Length` exceeds `limit` before anything is read.
+pub async fn reject_oversized_body<B>(req: Request<B>, next: Next<B>, limit: usize) -> Result<Response, ApiError> {
+    let too_large = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok())
+        .map(|length| length > limit as u64)
+        .unwrap_or(false);
+    if too_large {
+        return Err(ApiError::payload_too_large());
+    }
+    Ok(next.run(req).await)
+}
+
+/// Middleware answering requests that take longer than `limit` with a `408 Request Timeout`.
+pub async fn timeout<B>(req: Request<B>, next: Next<B>, limit: Duration) -> Result<Response, ApiError> {
+    tokio::time::timeout(limit, next.run(req))
+        .await
+        .map_err(|_| ApiError::new(StatusCode::REQUEST_TIMEOUT, format!("request took longer than {:?}", limit)))
+}
+
+/// Token buckets per client, each refilling at `rps` tokens per second up to `burst`.
+pub struct RateLimiter {
+    rps: f64,
+    burst: f64,
+    buckets: std::sync::Mutex<Buckets>,
+}
+
+#[derive(Default)]
+struct Buckets {
+    clients: HashMap<String, TokenBucket>,
+    /// When the full map was last searched for buckets to forget, at most once per `RATE_LIMIT_PRUNE_INTERVAL`.
+    pruned: Option<Instant>,
+}
+
+struct TokenBucket {
+    tokens: f64,
+    refilled: Instant,
+}
+
+/// Clients tracked before the buckets that have filled up again are forgotten. While none of them can be,
+/// the clients that don't fit share a single bucket.
+const RATE_LIMIT_MAX_CLIENTS: usize = 10_000;
+
+/// How often a full map of buckets is pruned, so a flood of new clients doesn't make every request scan it.
+const RATE_LIMIT_PRUNE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The bucket shared by the clients that arrive while `RATE_LIMIT_MAX_CLIENTS` are tracked.
+const OVERFLOW_CLIENT: &str = "overflow";
+
+impl RateLimiter {
+    pub fn new(rps: f64, burst: f64) -> Self {
+        RateLimiter { rps, burst, buckets: Default::default() }
+    }
+
+    /// Takes a token from the bucket of `client`, or returns how long until one is available.
+    fn check(&self, client: &str, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap();
+        let Buckets { clients, pruned } = &mut *buckets;
+        let mut client = client;
+        if clients.len() >= RATE_LIMIT_MAX_CLIENTS && !clients.contains_key(client) {
+            if pruned.is_none_or(|pruned| now.duration_since(pruned) >= RATE_LIMIT_PRUNE_INTERVAL) {
+                // a full bucket is indistinguishable from a new one
+                let full_after = Duration::from_secs_f64(self.burst / self.rps);
+                clients.retain(|_, bucket| now.duration_since(bucket.refilled) < full_after);
+                *pruned = Some(now);
+            }
+            if clients.len() >= RATE_LIMIT_MAX_CLIENTS {
+                client = OVERFLOW_CLIENT;
+            }
+        }
+
+        let bucket = clients
+            .entry(client.to_owned())
+            .or_insert(TokenBucket { tokens: self.burst, refilled: now });
+        let elapsed = now.duration_since(bucket.refilled).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rps).min(self.burst);
+        bucket.refilled = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.rps))
+        }
+    }
+}
+
+/// Middleware answering `429 Too Many Requests` to clients over their rate. Clients sending the configured
+/// `api_key` share one bucket, everyone else is told apart by IP address; any other `X-Api-Key` is ignored,
+/// as a made up key per request would otherwise get a fresh bucket every time.
+pub async fn rate_limit<B>(req: Request<B>, next: Next<B>, limiter: Arc<RateLimiter>, api_key: Option<Arc<str>>) -> Response {
+    let has_api_key = match (req.headers().get(&X_API_KEY), &api_key) {
+        (Some(provided), Some(api_key)) => constant_time_eq(provided.as_bytes(), api_key.as_bytes()),
+        _ => false,
+    };
+    let client = if has_api_key {
+        "key".to_owned()
+    } else {
+        match req.extensions().get::<ConnectInfo<SocketAddr>>() {
+            Some(ConnectInfo(addr)) => format!("ip:{}", addr.ip()),
+            None => "unknown".to_owned(),
+        }
+    };
+
+    match limiter.check(&client, Instant::now()) {
+        Ok(()) => next.run(req).await,
+        Err(wait) => {
+            let mut response = ApiError::new(StatusCode::TOO_MANY_REQUESTS, "too many requests").into_response();
+            // Retry-After is in whole seconds, rounded up so that the retry is not rejected again
+            let retry_after = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+            response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+            response
+        }
+    }
+}
+
+/// Header carrying the key that grants access to the write endpoints.
+pub const X_API_KEY: HeaderName = HeaderName::from_static("x-api-key");
+
+/// Middleware that lets reads through but rejects writes without the expected `X-Api-Key`.
+pub async fn require_api_key<B>(req: Request<B>, next: Next<B>, api_key: Arc<str>) -> Result<Response, ApiError> {
+    if matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS) {
+        return Ok(next.run(req).await);
+    }
+
+    let authorized = req
+        .headers()
+        .get(&X_API_KEY)
+        .map(|provided| constant_time_eq(provided.as_bytes(), api_key.as_bytes()))
+        .unwrap_or(false);
+    if !authorized {
+        return Err(ApiError::new(StatusCode::UNAUTHORIZED, "missing or invalid X-Api-Key"));
+    }
+    Ok(next.run(req).await)
+}
+
+/// Compares two byte strings in time independent of where they differ.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Header used to pass the request id in from upstream services and back to the client.
+pub const X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+tokio::task_local! {
+    /// Id of the request being handled, so that error responses can include it.
+    pub static REQUEST_ID: String;
+}
+
+/// Middleware that assigns every request an id, taken from `X-Request-Id` or generated,
+/// records it in the tracing span and echoes it in the response.
+pub async fn request_id<B>(req: Request<B>, next: Next<B>) -> Response {
+    let request_id = req
+        .headers()
+        .get(&X_REQUEST_ID)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.is_empty())
+        .map(str::to_owned)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let span = info_span!("request_id", request_id = %request_id);
+    let mut response = REQUEST_ID
+        .scope(request_id.clone(), next.run(req))
+        .instrument(span)
+        .await;
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(X_REQUEST_ID, value);
+    }
+    response
+}
+
+/// Middleware adding the headers that keep browsers from sniffing, framing or leaking the responses,
+/// and `csp` as the `Content-Security-Policy`.
+pub async fn security_headers<B>(req: Request<B>, next: Next<B>, csp: Option<HeaderValue>) -> Response {
+    // swagger ui is a page running its own scripts and styles, which the policy of the api would block
+    let is_docs = req.uri().path().starts_with("/swagger-ui");
+    let mut response = next.run(req).await;
+
+    let headers = response.headers_mut();
+    headers.insert(header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+    headers.insert(header::X_FRAME_OPTIONS, HeaderValue::from_static("DENY"));
+    headers.insert(header::REFERRER_POLICY, HeaderValue::from_static("no-referrer"));
+    if let Some(csp) = csp.filter(|_| !is_docs) {
+        headers.insert(header::CONTENT_SECURITY_POLICY, csp);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_limits_each_client_separately() {
+        let limiter = RateLimiter::new(2.0, 2.0);
+        let start = Instant::now();
+
+        assert!(limiter.check("a", start).is_ok());
+        assert!(limiter.check("a", start).is_ok());
+        assert_eq!(limiter.check("a", start), Err(Duration::from_millis(500)));
+        assert!(limiter.check("b", start).is_ok());
+        assert!(limiter.check("a", start + Duration::from_millis(500)).is_ok());
+    }
+
+    #[test]
+    fn shares_a_bucket_between_clients_that_do_not_fit() {
+        let limiter = RateLimiter::new(1.0, 1.0);
+        let start = Instant::now();
+        for client in 0..RATE_LIMIT_MAX_CLIENTS {
+            assert!(limiter.check(&client.to_string(), start).is_ok());
+        }
+
+        // none of the buckets filled up again yet, so the newcomers are limited together
+        assert!(limiter.check("new", start).is_ok());
+        assert!(limiter.check("newer", start).is_err());
+        assert!(limiter.buckets.lock().unwrap().clients.len() <= RATE_LIMIT_MAX_CLIENTS + 1);
+
+        // once they have, they are forgotten to make room
+        assert!(limiter.check("newest", start + Duration::from_secs(2)).is_ok());
+        assert_eq!(limiter.buckets.lock().unwrap().clients.len(), 1);
+    }
+}
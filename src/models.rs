@@ -0,0 +1,803 @@
+//! The entries and comments as they are stored and sent over the wire, with their query parameters.
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::{PgArguments, Postgres};
+use sqlx::query::QueryAs;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::error::ApiError;
+use crate::extract::Normalize;
+
+// utoipa 2 has no length constraints on schemas, so those are spelled out in the field docs
+#[derive(Serialize, Deserialize, Clone, Debug, sqlx::FromRow, Validate, ToSchema)]
+pub struct BlogEntry {
+    #[schema(example = "6f1c2b9e-8d4a-4e0f-9a57-3c2e1d0b7a64")]
+    pub id: Option<Uuid>,
+    /// Must not be in the future.
+    #[validate(custom = "validate_not_future")]
+    pub created: DateTime<Utc>,
+    /// Maintained by the server, any value sent by the client is ignored.
+    #[serde(skip_deserializing)]
+    #[schema(read_only)]
+    pub updated: Option<DateTime<Utc>>,
+    /// Between 10 and 100 characters.
+    #[validate(length(min = 10, max = 100, message = "Title length must be between 10 and 100"))]
+    #[schema(example = "Hello from the blog")]
+    pub title: String,
+    #[validate(email(message = "author must be a valid email address"))]
+    #[schema(format = "email", example = "author@example.com")]
+    pub author: String,
+    /// At least 10 characters.
+    #[validate(length(min = 10, message = "text length must be at least 10"))]
+    pub text: String,
+    /// Only set when the entry is created, a replacement keeps them.
+    #[serde(skip_deserializing)]
+    #[schema(read_only)]
+    pub tags: Vec<String>,
+    /// When the entry was deleted, only ever set in listings with `include_deleted`.
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    #[schema(read_only)]
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// Bumped on every update. When sent with a replacement it must match, like `If-Match`.
+    #[schema(example = 1)]
+    pub version: Option<i32>,
+    /// Derived from the title when the entry is created and kept after that, for `/entries/by-slug/{slug}`.
+    #[serde(skip_deserializing)]
+    #[schema(read_only, example = "hello-from-the-blog")]
+    pub slug: Option<String>,
+    /// Estimated from the word count of `text`, not stored.
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    #[sqlx(default)]
+    #[schema(read_only, example = 3)]
+    pub reading_time_minutes: Option<i32>,
+}
+
+/// Columns to select for a `BlogEntry`, the tags aggregated from the join table.
+pub const ENTRY_COLUMNS: &str = "id, created, updated, title, author, text, deleted_at, version, slug, \
+    array(select t.name from blog_entry_tags bt join tags t on t.id = bt.tag_id where bt.blog_entry_id = blog_entry.id order by t.name) as tags";
+
+/// How far ahead of the server clock a client timestamp may be, to allow for clock skew.
+const FUTURE_TOLERANCE_SECS: i64 = 60;
+
+/// Rejects timestamps more than `FUTURE_TOLERANCE_SECS` ahead of now.
+fn validate_not_future(timestamp: &DateTime<Utc>) -> Result<(), validator::ValidationError> {
+    if *timestamp > Utc::now() + chrono::Duration::seconds(FUTURE_TOLERANCE_SECS) {
+        let mut error = validator::ValidationError::new("not_future");
+        error.message = Some("created must not be in the future".into());
+        return Err(error);
+    }
+    Ok(())
+}
+
+/// Words read per minute for the `reading_time_minutes` of an entry.
+const WORDS_PER_MINUTE: usize = 200;
+
+impl BlogEntry {
+    /// Fills in `reading_time_minutes` from the text, rounding up so even the shortest entry takes a minute.
+    pub fn with_reading_time(self) -> BlogEntry {
+        let words = self.text.split_whitespace().count();
+        let minutes = words.div_ceil(WORDS_PER_MINUTE).max(1);
+        BlogEntry { reading_time_minutes: Some(minutes as i32), ..self }
+    }
+
+    /// Strong ETag for this version of the entry, `"<id>-<version>"`; `If-Match` takes it back.
+    pub fn etag(&self) -> Option<String> {
+        Some(format!("\"{}-{}\"", self.id?, self.version?))
+    }
+}
+
+/// Input for a new entry, `created` is set by the server and `author` is the signed in user.
+#[derive(Deserialize, Clone, Debug, Validate, ToSchema)]
+pub struct NewBlogEntry {
+    /// Between 10 and 100 characters.
+    #[validate(length(min = 10, max = 100, message = "Title length must be between 10 and 100"))]
+    #[schema(example = "Hello from the blog")]
+    pub title: String,
+    /// At least 10 characters.
+    #[validate(length(min = 10, message = "text length must be at least 10"))]
+    pub text: String,
+    /// Up to 20 tags of at most 40 characters, stored in lowercase.
+    #[serde(default)]
+    #[validate(custom = "validate_tags")]
+    #[schema(example = json!(["rust", "axum"]))]
+    pub tags: Vec<String>,
+}
+
+const MAX_TAGS: usize = 20;
+const MAX_TAG_LENGTH: usize = 40;
+
+fn validate_tags(tags: &[String]) -> Result<(), validator::ValidationError> {
+    let message = if tags.len() > MAX_TAGS {
+        format!("at most {} tags are allowed", MAX_TAGS)
+    } else if tags.iter().any(|tag| tag.is_empty() || tag.chars().count() > MAX_TAG_LENGTH) {
+        format!("tags must be between 1 and {} characters", MAX_TAG_LENGTH)
+    } else {
+        return Ok(());
+    };
+    let mut error = validator::ValidationError::new("tags");
+    error.message = Some(message.into());
+    Err(error)
+}
+
+/// A comment on an entry, deleted along with it.
+#[derive(Serialize, Clone, Debug, sqlx::FromRow, ToSchema)]
+pub struct Comment {
+    id: i64,
+    entry_id: Uuid,
+    /// The signed in user that wrote the comment.
+    #[schema(format = "email", example = "reader@example.com")]
+    author: String,
+    body: String,
+    created: DateTime<Utc>,
+}
+
+/// What an author has published, deleted entries left out.
+#[derive(Serialize, Debug, sqlx::FromRow, ToSchema)]
+pub struct AuthorStats {
+    #[schema(format = "email", example = "author@example.com")]
+    author: String,
+    entry_count: i64,
+    first_post: DateTime<Utc>,
+    last_post: DateTime<Utc>,
+}
+
+/// Input for a new comment, `author` is the signed in user.
+#[derive(Deserialize, Clone, Debug, Validate, ToSchema)]
+pub struct NewComment {
+    /// Between 1 and 2000 characters.
+    #[validate(length(min = 1, max = 2000, message = "body length must be between 1 and 2000"))]
+    pub body: String,
+}
+
+/// How many entries one author published, deleted entries left out.
+#[derive(Serialize, Debug, sqlx::FromRow, ToSchema)]
+pub struct AuthorCount {
+    #[schema(format = "email", example = "author@example.com")]
+    author: String,
+    count: i64,
+}
+
+#[derive(Deserialize, Debug, Default, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuthorCountParams {
+    /// Only this many of the most prolific authors, all of them by default.
+    #[validate(range(min = 1, message = "limit must be at least 1"))]
+    pub limit: Option<i64>,
+}
+
+/// Moves every entry of an author over to a new email address.
+#[derive(Deserialize, Clone, Debug, Validate, ToSchema)]
+pub struct AuthorReassignment {
+    #[validate(email(message = "new_email must be a valid email address"))]
+    #[schema(format = "email", example = "new@example.com")]
+    pub new_email: String,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct Reassigned {
+    pub updated: u64,
+}
+
+/// A run of the search index maintenance, see `POST /admin/reindex`.
+#[derive(Serialize, Clone, Debug, ToSchema)]
+pub struct ReindexJob {
+    pub id: Uuid,
+    pub status: JobStatus,
+    pub started: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished: Option<DateTime<Utc>>,
+    /// How long the reindex and analyze took, once finished.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// A batch of new entries, sent as a plain JSON array.
+#[derive(Deserialize, Clone, Debug, Validate)]
+#[serde(transparent)]
+pub struct NewBlogEntries {
+    #[validate]
+    pub entries: Vec<NewBlogEntry>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct BulkInserted {
+    pub inserted: u64,
+}
+
+/// The version an entry has after an update, to send along with the next one.
+#[derive(Serialize, Debug, ToSchema)]
+pub struct Versioned {
+    #[schema(example = 2)]
+    pub version: i32,
+}
+
+/// Partial update of an entry, only the fields that are present are changed.
+#[derive(Deserialize, Clone, Debug, Validate, ToSchema)]
+pub struct BlogEntryPatch {
+    /// Between 10 and 100 characters.
+    #[validate(length(min = 10, max = 100, message = "Title length must be between 10 and 100"))]
+    pub title: Option<String>,
+    #[validate(email(message = "author must be a valid email address"))]
+    #[schema(format = "email")]
+    pub author: Option<String>,
+    /// At least 10 characters.
+    #[validate(length(min = 10, message = "text length must be at least 10"))]
+    pub text: Option<String>,
+    /// When given it must match the current version of the entry, like `If-Match`.
+    pub version: Option<i32>,
+}
+
+const DEFAULT_PER_PAGE: u32 = 50;
+
+#[derive(Deserialize, Debug, Default, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Pagination {
+    /// Zero based page number, defaults to 0.
+    page: Option<u32>,
+    /// Entries per page, defaults to 50. Larger values are capped at `MAX_PAGE_SIZE`, 200 unless configured otherwise;
+    /// the `per_page` of the response tells the size that was used.
+    // signed, so a negative size gets the same error as 0 rather than a parse error
+    #[validate(range(min = 1, message = "per_page must be at least 1"))]
+    per_page: Option<i64>,
+    /// The `next_cursor` of the previous page, to continue after it instead of jumping to `page`.
+    /// Only works with the default `-created` sort.
+    after: Option<String>,
+}
+
+impl Pagination {
+    /// Resolves the validated `(page, per_page)`, applying the defaults and capping the page size at `max_per_page`.
+    pub fn resolve(&self, max_per_page: u32) -> (u32, u32) {
+        let page = self.page.unwrap_or(0);
+        let per_page = match self.per_page {
+            Some(per_page) => per_page.clamp(1, max_per_page as i64) as u32,
+            None => DEFAULT_PER_PAGE.min(max_per_page),
+        };
+        (page, per_page)
+    }
+
+    /// Decodes the `after` cursor, if one was sent.
+    pub fn cursor(&self) -> Result<Option<Cursor>, ApiError> {
+        match &self.after {
+            Some(_) if self.page.is_some() => Err(ApiError::bad_request("page and after can't be combined")),
+            Some(after) => Cursor::decode(after).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Position of the last entry seen in the default `-created` order, sent to clients as an opaque string.
+/// Unlike an offset it stays put when new entries come in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor {
+    pub created: DateTime<Utc>,
+    pub id: Uuid,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        let position = format!("{},{}", self.created.to_rfc3339_opts(SecondsFormat::Micros, true), self.id);
+        base64::encode_config(position, base64::URL_SAFE_NO_PAD)
+    }
+
+    pub fn decode(cursor: &str) -> Result<Cursor, ApiError> {
+        let invalid = || ApiError::bad_request(format!("invalid cursor {:?}", cursor));
+        let position = base64::decode_config(cursor, base64::URL_SAFE_NO_PAD).map_err(|_| invalid())?;
+        let position = String::from_utf8(position).map_err(|_| invalid())?;
+        let (created, id) = position.split_once(',').ok_or_else(invalid)?;
+        Ok(Cursor {
+            created: DateTime::parse_from_rfc3339(created).map_err(|_| invalid())?.with_timezone(&Utc),
+            id: id.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// One page of a listing, with enough information for the client to fetch the others.
+#[derive(Serialize, Debug, ToSchema)]
+#[aliases(EntryPage = Page<BlogEntry>)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub page: u32,
+    pub per_page: u32,
+    pub total: i64,
+    /// Pass as `after` to get the next page, `null` on the last page or when not sorting by `-created`.
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Sorting {
+    /// One of `created`, `-created`, `title` or `-title`, defaults to `-created`.
+    sort: Option<String>,
+}
+
+impl Sorting {
+    /// Maps the `sort` key to an `order by` clause; a leading `-` sorts descending.
+    /// Only these hard-coded clauses ever reach the sql.
+    pub fn order_by(&self) -> Result<&'static str, ApiError> {
+        match self.sort.as_deref() {
+            // the id breaks ties, so cursors point at a single entry
+            None | Some("-created") => Ok("created desc, id desc"),
+            Some("created") => Ok("created asc"),
+            Some("title") => Ok("title asc"),
+            Some("-title") => Ok("title desc"),
+            Some(other) => Err(ApiError::bad_request(format!(
+                "unknown sort key {:?}, expected one of created, -created, title, -title",
+                other
+            ))),
+        }
+    }
+
+    /// Whether entries are listed newest first, the only order cursors work with.
+    pub fn newest_first(&self) -> bool {
+        matches!(self.sort.as_deref(), None | Some("-created"))
+    }
+}
+
+#[derive(Deserialize, Debug, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct FieldSelection {
+    /// Comma separated fields to return for each entry, e.g. `id,title`; every field by default.
+    fields: Option<String>,
+}
+
+/// The fields of a `BlogEntry` that can be selected, with the sql selecting each one and a cheap stand-in
+/// for when it isn't. The ones the listing itself needs, for cursors and `Last-Modified`, are always selected.
+const ENTRY_FIELDS: &[(&str, &str, &str)] = &[
+    ("id", "id", "id"),
+    ("created", "created", "created"),
+    ("updated", "updated", "updated"),
+    ("title", "title", "''::text as title"),
+    ("author", "author", "''::text as author"),
+    ("text", "text", "''::text as text"),
+    ("deleted_at", "deleted_at", "null::timestamptz as deleted_at"),
+    ("version", "version", "null::integer as version"),
+    ("slug", "slug", "null::text as slug"),
+    (
+        "tags",
+        "array(select t.name from blog_entry_tags bt join tags t on t.id = bt.tag_id where bt.blog_entry_id = blog_entry.id order by t.name) as tags",
+        "array[]::text[] as tags",
+    ),
+];
+
+/// The fields of a `BlogEntry` computed after it is fetched, with the field of `ENTRY_FIELDS` each one needs.
+const COMPUTED_ENTRY_FIELDS: &[(&str, &str)] = &[("reading_time_minutes", "text")];
+
+impl FieldSelection {
+    /// The fields asked for, `None` for all of them. Unknown fields are rejected.
+    pub fn resolve(&self) -> Result<Option<Fields>, ApiError> {
+        let fields = match &self.fields {
+            Some(fields) => fields,
+            None => return Ok(None),
+        };
+        let mut selected = Vec::new();
+        let known = || ENTRY_FIELDS.iter().map(|(name, ..)| *name).chain(COMPUTED_ENTRY_FIELDS.iter().map(|(name, _)| *name));
+        for field in fields.split(',').map(str::trim) {
+            match known().find(|name| *name == field) {
+                Some(name) if !selected.contains(&name) => selected.push(name),
+                Some(_) => {}
+                None => {
+                    let known: Vec<_> = known().collect();
+                    return Err(ApiError::bad_request(format!("unknown field {:?}, expected some of {}", field, known.join(", "))));
+                }
+            }
+        }
+        Ok(Some(Fields(selected)))
+    }
+}
+
+/// A validated selection of `ENTRY_FIELDS`.
+#[derive(Debug, Clone)]
+pub struct Fields(Vec<&'static str>);
+
+impl Fields {
+    /// The columns to select for a `BlogEntry`, stand-ins for the fields that weren't asked for nor are needed
+    /// to compute one that was. Only the hard-coded sql of `ENTRY_FIELDS` ever ends up in the query.
+    pub fn columns(&self) -> String {
+        let needed = |name: &str| {
+            self.0.contains(&name)
+                || matches!(name, "id" | "created" | "updated")
+                || COMPUTED_ENTRY_FIELDS.iter().any(|(computed, source)| *source == name && self.0.contains(computed))
+        };
+        ENTRY_FIELDS
+            .iter()
+            .map(|(name, column, stand_in)| if needed(name) { *column } else { *stand_in })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// `entry` as a JSON object holding just the selected fields.
+    pub fn project(&self, entry: &BlogEntry) -> serde_json::Value {
+        let mut object = match serde_json::to_value(entry) {
+            Ok(serde_json::Value::Object(object)) => object,
+            _ => unreachable!("an entry serializes to an object"),
+        };
+        object.retain(|field, _| self.0.contains(&field.as_str()));
+        serde_json::Value::Object(object)
+    }
+}
+
+/// Optional filters on the entry list. Values are always bound as parameters, never interpolated.
+#[derive(Deserialize, Debug, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EntryFilter {
+    /// Only entries by this author, ignoring case.
+    author: Option<String>,
+    /// Only entries with this text somewhere in their title, ignoring case.
+    title_contains: Option<String>,
+    /// Only entries carrying this tag.
+    tag: Option<String>,
+    /// Only entries created at or after this time.
+    from: Option<DateTime<Utc>>,
+    /// Only entries created at or before this time.
+    to: Option<DateTime<Utc>>,
+    /// Also list deleted entries, for admins only.
+    #[serde(default)]
+    pub include_deleted: bool,
+}
+
+impl EntryFilter {
+    /// Rejects filters that would match everything or nothing by accident.
+    pub fn validate(&self) -> Result<(), ApiError> {
+        if matches!(&self.title_contains, Some(term) if term.trim().is_empty()) {
+            return Err(ApiError::bad_request("title_contains must not be empty"));
+        }
+        if let (Some(from), Some(to)) = (self.from, self.to) {
+            if from > to {
+                return Err(ApiError::bad_request("from must not be after to"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Renders the `where` clause for the filters that are present, numbering parameters from `$1`.
+    /// Also returns the number of the first parameter left free for the rest of the query.
+    pub fn where_clause(&self) -> (String, usize) {
+        let mut conditions = Vec::new();
+        let mut next_param = 1;
+        if !self.include_deleted {
+            conditions.push("deleted_at is null".to_owned());
+        }
+        if self.author.is_some() {
+            conditions.push(format!("lower(author) = lower(${})", next_param));
+            next_param += 1;
+        }
+        if self.title_contains.is_some() {
+            conditions.push(format!("title ilike ${} escape '\\'", next_param));
+            next_param += 1;
+        }
+        if self.tag.is_some() {
+            conditions.push(format!(
+                "exists (select 1 from blog_entry_tags bt join tags t on t.id = bt.tag_id \
+                 where bt.blog_entry_id = blog_entry.id and t.name = ${})",
+                next_param
+            ));
+            next_param += 1;
+        }
+        if self.from.is_some() {
+            conditions.push(format!("created >= ${}", next_param));
+            next_param += 1;
+        }
+        if self.to.is_some() {
+            conditions.push(format!("created <= ${}", next_param));
+            next_param += 1;
+        }
+
+        if conditions.is_empty() {
+            (String::new(), next_param)
+        } else {
+            (format!(" where {}", conditions.join(" and ")), next_param)
+        }
+    }
+
+    /// Binds the filter values in the same order as `where_clause` numbers them.
+    pub fn bind<'q, O>(&'q self, mut query: QueryAs<'q, Postgres, O, PgArguments>) -> QueryAs<'q, Postgres, O, PgArguments> {
+        if let Some(author) = &self.author {
+            query = query.bind(author);
+        }
+        if let Some(term) = &self.title_contains {
+            query = query.bind(format!("%{}%", escape_like(term.trim())));
+        }
+        if let Some(tag) = &self.tag {
+            // tags are stored in lowercase
+            query = query.bind(tag.trim().to_lowercase());
+        }
+        if let Some(from) = self.from {
+            query = query.bind(from);
+        }
+        if let Some(to) = self.to {
+            query = query.bind(to);
+        }
+        query
+    }
+}
+
+/// Escapes the `like` wildcards in `term`, so it is matched literally.
+fn escape_like(term: &str) -> String {
+    let mut escaped = String::with_capacity(term.len());
+    for c in term.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[derive(Deserialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SearchParams {
+    /// Words to look for in the title and text.
+    pub q: String,
+}
+
+impl Normalize for BlogEntry {
+    fn normalize(&mut self) {
+        self.title = collapse_whitespace(&self.title);
+        self.author = normalize_email(&self.author);
+        trim_in_place(&mut self.text);
+    }
+
+    fn sanitize_html(&mut self) {
+        self.text = sanitize_html(&self.text);
+    }
+}
+
+impl Normalize for NewBlogEntry {
+    fn normalize(&mut self) {
+        self.title = collapse_whitespace(&self.title);
+        trim_in_place(&mut self.text);
+        self.tags = normalize_tags(&self.tags);
+    }
+
+    fn sanitize_html(&mut self) {
+        self.text = sanitize_html(&self.text);
+    }
+}
+
+impl Normalize for AuthorReassignment {
+    fn normalize(&mut self) {
+        self.new_email = normalize_email(&self.new_email);
+    }
+}
+
+impl Normalize for NewComment {
+    fn normalize(&mut self) {
+        trim_in_place(&mut self.body);
+    }
+}
+
+impl Normalize for NewBlogEntries {
+    fn normalize(&mut self) {
+        self.entries.iter_mut().for_each(Normalize::normalize);
+    }
+
+    fn sanitize_html(&mut self) {
+        self.entries.iter_mut().for_each(Normalize::sanitize_html);
+    }
+}
+
+impl Normalize for BlogEntryPatch {
+    fn normalize(&mut self) {
+        if let Some(title) = &mut self.title {
+            *title = collapse_whitespace(title);
+        }
+        if let Some(author) = &mut self.author {
+            *author = normalize_email(author);
+        }
+        if let Some(text) = &mut self.text {
+            trim_in_place(text);
+        }
+    }
+
+    fn sanitize_html(&mut self) {
+        if let Some(text) = &mut self.text {
+            *text = sanitize_html(text);
+        }
+    }
+}
+
+/// Markup kept by `sanitize_html`: links, emphasis, lists, headings and plain text structure.
+const SAFE_TAGS: &[&str] = &[
+    "a", "b", "strong", "i", "em", "del", "code", "pre", "blockquote", "p", "br", "hr", "ul", "ol", "li", "h1", "h2", "h3", "h4", "h5", "h6",
+];
+
+/// Strips every tag but `SAFE_TAGS` from `text`, along with the attributes that can run scripts, e.g. `onclick`
+/// or a `javascript:` link. The contents of `script` and `style` go too, those of other tags are kept.
+fn sanitize_html(text: &str) -> String {
+    ammonia::Builder::default()
+        .tags(SAFE_TAGS.iter().copied().collect())
+        .clean(text)
+        .to_string()
+        .trim()
+        .to_owned()
+}
+
+/// `text` as markdown rendered to HTML, put through `sanitize_html` as markdown lets raw HTML through.
+pub fn render_markdown(text: &str) -> String {
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, pulldown_cmark::Parser::new_ext(text, pulldown_cmark::Options::ENABLE_STRIKETHROUGH));
+    sanitize_html(&html)
+}
+
+/// Trims leading and trailing whitespace and replaces every inner run of whitespace by a single space.
+fn collapse_whitespace(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Trims and lowercases the tags, dropping duplicates but keeping the order they were given in.
+fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::with_capacity(tags.len());
+    for tag in tags {
+        let tag = collapse_whitespace(tag).to_lowercase();
+        if !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    normalized
+}
+
+/// Trims and lowercases an email address, so one author isn't stored under several spellings.
+/// Case never makes an address valid or invalid, so this is safe to do before it is validated.
+pub fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+/// Longest slug made from a title, before a counter is appended to tell it apart from an existing one.
+pub const MAX_SLUG_LENGTH: usize = 80;
+
+/// The title in lowercase with every run of other characters than letters and digits turned into one hyphen,
+/// e.g. `"Hello, World!"` becomes `hello-world`. Letters and digits of any script are kept, and long titles
+/// are cut off at `MAX_SLUG_LENGTH` characters. A title without any letters or digits becomes `entry`.
+pub fn slugify(title: &str) -> String {
+    let mut slug = String::new();
+    for c in title.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug: String = slug.chars().take(MAX_SLUG_LENGTH).collect();
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() { "entry".to_owned() } else { slug.to_owned() }
+}
+
+fn trim_in_place(value: &mut String) {
+    let trimmed = value.trim();
+    if trimmed.len() != value.len() {
+        *value = trimmed.to_owned();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_new_entries() {
+        let mut entry = NewBlogEntry {
+            title: "  A   padded\ttitle ".to_owned(),
+            text: "\n some text  \n".to_owned(),
+            tags: vec![" Rust".to_owned(), "rust ".to_owned(), "Web  Dev".to_owned()],
+        };
+        entry.normalize();
+
+        assert_eq!(entry.title, "A padded title");
+        assert_eq!(entry.text, "some text");
+        assert_eq!(entry.tags, ["rust", "web dev"]);
+    }
+
+    #[test]
+    fn escapes_like_wildcards() {
+        assert_eq!(escape_like(r"50%_off\"), r"50\%\_off\\");
+    }
+
+    #[test]
+    fn round_trips_cursors() {
+        let cursor = Cursor { created: "2023-06-01T12:00:00.123456Z".parse().unwrap(), id: Uuid::new_v4() };
+        assert_eq!(Cursor::decode(&cursor.encode()).unwrap(), cursor);
+        assert!(Cursor::decode("not a cursor").is_err());
+        assert!(Cursor::decode(&base64::encode_config("2023-06-01,42", base64::URL_SAFE_NO_PAD)).is_err());
+    }
+
+    #[test]
+    fn selects_only_known_fields() {
+        let selection = FieldSelection { fields: Some("id, title,id".to_owned()) };
+        let fields = selection.resolve().unwrap().unwrap();
+        assert_eq!(fields.columns(), "id, created, updated, title, ''::text as author, ''::text as text, null::timestamptz as deleted_at, null::integer as version, null::text as slug, array[]::text[] as tags");
+
+        // the reading time is computed from the text
+        let selection = FieldSelection { fields: Some("reading_time_minutes".to_owned()) };
+        assert!(selection.resolve().unwrap().unwrap().columns().contains(", text, "));
+
+        let selection = FieldSelection { fields: Some("id,password".to_owned()) };
+        assert!(selection.resolve().is_err());
+    }
+
+    #[test]
+    fn caps_the_page_size() {
+        let pagination = |per_page| Pagination { per_page: Some(per_page), ..Default::default() };
+        assert_eq!(pagination(1_000_000).resolve(200), (0, 200));
+        assert_eq!(pagination(20).resolve(200), (0, 20));
+        assert!(pagination(20).validate().is_ok());
+        assert!(pagination(0).validate().is_err());
+        assert!(pagination(-5).validate().is_err());
+        assert_eq!(Pagination::default().resolve(10), (0, 10));
+    }
+
+    #[test]
+    fn estimates_the_reading_time() {
+        let entry = |words: usize| BlogEntry {
+            id: None,
+            created: Utc::now(),
+            updated: None,
+            title: "A long enough title".to_owned(),
+            author: "author@example.com".to_owned(),
+            text: "word ".repeat(words),
+            tags: Vec::new(),
+            deleted_at: None,
+            version: None,
+            slug: None,
+            reading_time_minutes: None,
+        };
+        assert_eq!(entry(3).with_reading_time().reading_time_minutes, Some(1));
+        assert_eq!(entry(200).with_reading_time().reading_time_minutes, Some(1));
+        assert_eq!(entry(201).with_reading_time().reading_time_minutes, Some(2));
+        assert_eq!(entry(1000).with_reading_time().reading_time_minutes, Some(5));
+    }
+
+    #[test]
+    fn sanitizes_html_in_the_text() {
+        let mut entry: NewBlogEntry = serde_json::from_str(
+            r#"{"title": "A long enough title", "text": "<p onclick=\"steal()\">Read <b>this</b> <a href=\"javascript:steal()\">now</a></p><script>steal()</script><ul><li><a href=\"https://example.com\">here</a></li></ul><img src=x>"}"#,
+        )
+        .unwrap();
+        entry.sanitize_html();
+        assert_eq!(
+            entry.text,
+            r#"<p>Read <b>this</b> <a rel="noopener noreferrer">now</a></p><ul><li><a href="https://example.com" rel="noopener noreferrer">here</a></li></ul>"#
+        );
+    }
+
+    #[test]
+    fn renders_markdown_to_safe_html() {
+        assert_eq!(
+            render_markdown("# Title\n\nSome **bold** and [a link](https://example.com).\n\n* one\n* two\n\n<script>steal()</script>"),
+            "<h1>Title</h1>\n<p>Some <strong>bold</strong> and <a href=\"https://example.com\" rel=\"noopener noreferrer\">a link</a>.</p>\n<ul>\n<li>one</li>\n<li>two</li>\n</ul>"
+        );
+    }
+
+    #[test]
+    fn slugifies_titles() {
+        assert_eq!(slugify("Hello, World!"), "hello-world");
+        assert_eq!(slugify("  -- Rust & Axum: 2 ways --  "), "rust-axum-2-ways");
+        assert_eq!(slugify("Über Straße"), "über-straße");
+        assert_eq!(slugify("日本語のブログ"), "日本語のブログ");
+        assert_eq!(slugify("?!"), "entry");
+
+        let long = slugify(&"abcd ".repeat(40));
+        assert_eq!(long.chars().count(), MAX_SLUG_LENGTH - 1);
+        assert!(!long.ends_with('-'));
+        assert_eq!(slugify(&"é".repeat(200)).chars().count(), MAX_SLUG_LENGTH);
+    }
+
+    #[test]
+    fn lowercases_authors() {
+        let mut patch: BlogEntryPatch = serde_json::from_str(r#"{"author": " Foo@Example.com "}"#).unwrap();
+        patch.normalize();
+        assert_eq!(patch.author.as_deref(), Some("foo@example.com"));
+    }
+}
@@ -0,0 +1,3106 @@
+//! The api routes, their handlers and the `Router` that wires them up with the middleware.
+
+use std::{convert::Infallible, future::Future, sync::Arc, time::{Duration, Instant}};
+use axum::{http::{header::{self, HeaderName}, HeaderMap, HeaderValue, Method, StatusCode, Uri}, middleware, Json, response::{Html, IntoResponse, Response}, Router, routing::MethodRouter, BoxError};
+use axum::body::StreamBody;
+use axum::handler::Handler;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::extract::{Extension, OriginalUri, Path, Query};
+use chrono::{DateTime, SubsecRound, TimeZone, Utc};
+use futures::{Stream, TryStreamExt};
+use serde::Serialize;
+use jsonwebtoken::DecodingKey;
+use sqlx::postgres::{PgListener, PgPool, Postgres};
+use sqlx::Transaction;
+use tokio::sync::{broadcast, watch};
+use tower_http::compression::CompressionLayer;
+use tower_http::compression::predicate::{DefaultPredicate, NotForContentType, Predicate};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tower_http::limit::RequestBodyLimitLayer;
+use tower_http::trace::{DefaultMakeSpan, DefaultOnResponse, TraceLayer};
+use tower_http::LatencyUnit;
+use tracing::{debug, info, warn};
+use utoipa::{Modify, OpenApi, ToSchema};
+use utoipa::openapi::Server;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa_swagger_ui::SwaggerUi;
+use uuid::Uuid;
+
+use crate::config::redact_passwords;
+use crate::error::{ApiError, ServerError, internal_error, map_db_error};
+use crate::extract::{AdminUser, AuthUser, ValidatedJson, ValidatedQuery};
+use crate::middleware::{RateLimiter, X_API_KEY, X_REQUEST_ID, rate_limit, reject_oversized_body, request_id, require_api_key, security_headers, timeout, track_metrics};
+use crate::models::{AuthorCount, AuthorCountParams, AuthorReassignment, AuthorStats, BlogEntry, BlogEntryPatch, BulkInserted, Comment, Cursor, ENTRY_COLUMNS, EntryFilter, EntryPage, FieldSelection, Fields, JobStatus, NewBlogEntries, NewBlogEntry, NewComment, Page, Pagination, Reassigned, ReindexJob, SearchParams, Sorting, Versioned, normalize_email, render_markdown, slugify};
+use crate::state::AppState;
+
+/// Builds the application `Router` with all routes and middleware.
+pub fn app(state: AppState) -> Router {
+    let config = state.config.clone();
+    let max_body_bytes = config.max_body_bytes;
+    let content_security_policy = config.content_security_policy.clone();
+    let request_timeout = config.request_timeout;
+
+    let allowed_origins = match &config.allowed_origins {
+        Some(origins) => AllowOrigin::list(origins.iter().cloned()),
+        None => {
+            warn!("ALLOWED_ORIGINS is not set, allowing requests from any origin");
+            AllowOrigin::any()
+        }
+    };
+    let cors = CorsLayer::new()
+        .allow_origin(allowed_origins)
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE, Method::PATCH])
+        .allow_headers([
+            header::AUTHORIZATION,
+            header::CONTENT_TYPE,
+            header::IF_MATCH,
+            header::IF_NONE_MATCH,
+            header::IF_MODIFIED_SINCE,
+            X_API_KEY,
+            IDEMPOTENCY_KEY,
+        ])
+        // without these, browsers hide the pagination, caching and tracing headers from scripts
+        .expose_headers([X_TOTAL_COUNT, header::LINK, header::ETAG, header::LAST_MODIFIED, header::LOCATION, X_REQUEST_ID])
+        // the layer replaces the Vary of every response, so Accept is listed here: the entry list is JSON or
+        // NDJSON depending on it, and shared caches have to keep the two apart
+        .vary([header::ORIGIN, header::ACCESS_CONTROL_REQUEST_METHOD, header::ACCESS_CONTROL_REQUEST_HEADERS, header::ACCEPT]);
+
+    let mut api = Router::new()
+        .route("/entries", methods().get(get_blogs).head(count_blogs).post(add_blog).finish())
+        .route("/entries.csv", methods().get(export_csv).finish())
+        .route("/entries/bulk", methods().post(add_blogs_bulk).finish())
+        .route("/entries/validate", methods().post(validate_blog).finish())
+        .route("/entries/events", methods().get(entry_events).finish())
+        .route("/entries/stream", methods().get(entry_stream).finish())
+        .route("/entries/search", methods().get(search_blogs).finish())
+        .route("/entries/latest", methods().get(latest_blog).finish())
+        .route("/entries/random", methods().get(random_blog).finish())
+        .route("/entries/by-slug/:slug", methods().get(get_blog_by_slug).finish())
+        .route("/entries/:id", methods().get(get_blog).put(update_blog).patch(patch_blog).delete(delete_blog).finish())
+        .route("/entries/:id/html", methods().get(get_blog_html).finish())
+        .route("/entries/:id/restore", methods().post(restore_blog).finish())
+        .route("/entries/:id/comments", methods().get(get_comments).post(add_comment).finish())
+        .route("/authors/counts", methods().get(author_counts).finish())
+        .route("/authors/:email", methods().patch(reassign_author).finish())
+        .route("/authors/:email/stats", methods().get(author_stats).finish())
+        .route("/admin/reindex", methods().post(start_reindex).finish())
+        .route("/admin/reindex/:id", methods().get(get_reindex).finish());
+
+    match &config.api_key {
+        Some(api_key) => {
+            let api_key: Arc<str> = api_key.as_str().into();
+            api = api.layer(middleware::from_fn(move |req, next| require_api_key(req, next, api_key.clone())));
+        }
+        None => warn!("API_KEY is not set, write endpoints are open to everyone"),
+    }
+
+    match &config.jwt_secret {
+        Some(secret) => api = api.layer(Extension(DecodingKey::from_secret(secret.as_bytes()))),
+        None => warn!("JWT_SECRET is not set, endpoints requiring a signed in user will reject every request"),
+    }
+
+    match config.rate_limit {
+        Some(limit) => {
+            let limiter = Arc::new(RateLimiter::new(limit.rps, limit.burst));
+            let api_key: Option<Arc<str>> = config.api_key.as_deref().map(Into::into);
+            api = api.layer(middleware::from_fn(move |req, next| rate_limit(req, next, limiter.clone(), api_key.clone())));
+        }
+        None => info!("RATE_LIMIT_RPS is not set, requests are not rate limited"),
+    }
+
+    let api = api
+        .layer(middleware::from_fn(move |req, next| timeout(req, next, request_timeout)))
+        .layer(middleware::from_fn(track_metrics));
+
+    let mut api_doc = ApiDoc::openapi();
+    let api = match &config.api_prefix {
+        Some(prefix) => {
+            // the documented paths are relative to the prefix, the server tells swagger where they live
+            api_doc.servers = Some(vec![Server::new(prefix)]);
+            Router::new().nest(prefix, api)
+        }
+        None => api,
+    };
+
+    // the probes, the version, the scrape endpoint and the api docs stay outside the api routes and the prefix,
+    // so they are neither authenticated, rate limited nor measured
+    Router::new()
+        .route("/health", methods().get(health).finish())
+        .route("/health/detailed", methods().get(detailed_health).finish())
+        .route("/version", methods().get(version).finish())
+        .route("/metrics", methods().get(metrics_handler).finish())
+        .merge(SwaggerUi::new("/swagger-ui/*tail").url("/api-docs/openapi.json", api_doc))
+        .merge(api)
+        .fallback(not_found.into_service())
+        .layer(Extension(state))
+        .layer(cors)
+        // event streams are left alone, a compressor would hold events back until its buffer fills
+        .layer(CompressionLayer::new().compress_when(DefaultPredicate::new().and(NotForContentType::const_new("text/event-stream"))))
+        // bodies without a content length are cut off while they are read, the extractors then reject them
+        .layer(RequestBodyLimitLayer::new(max_body_bytes))
+        .layer(middleware::from_fn(move |req, next| reject_oversized_body(req, next, max_body_bytes)))
+        .layer(TraceLayer::new_for_http()
+            .make_span_with(DefaultMakeSpan::new().level(config.http_log_level))
+            .on_response(DefaultOnResponse::new().level(config.http_log_level).latency_unit(LatencyUnit::Millis)))
+        .layer(middleware::from_fn(move |req, next| security_headers(req, next, content_security_policy.clone())))
+        .layer(middleware::from_fn(request_id))
+}
+
+/// OpenAPI description of the api routes, served at `/api-docs/openapi.json` and browsable at `/swagger-ui`.
+#[derive(OpenApi)]
+#[openapi(
+    paths(get_blogs, count_blogs, export_csv, get_blog, get_blog_by_slug, get_blog_html, latest_blog, random_blog, search_blogs, add_blog, validate_blog, add_blogs_bulk, update_blog, patch_blog, delete_blog, restore_blog, get_comments, add_comment, author_stats, author_counts, reassign_author, start_reindex, get_reindex, entry_events, entry_stream, health, detailed_health, version),
+    components(schemas(BlogEntry, NewBlogEntry, BlogEntryPatch, EntryPage, BulkInserted, Versioned, Comment, NewComment, AuthorStats, AuthorCount, AuthorReassignment, Reassigned, ReindexJob, JobStatus, Health, DetailedHealth, PoolHealth, ComponentHealth, MigrationHealth, Version)),
+    modifiers(&SecurityAddon),
+)]
+struct ApiDoc;
+
+/// Registers the `X-Api-Key` header and the bearer token guarding the write endpoints.
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme("api_key", SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new(X_API_KEY.as_str()))));
+            components.add_security_scheme(
+                "bearer",
+                SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).bearer_format("JWT").build()),
+            );
+        }
+    }
+}
+
+/// Changes buffered per change feed subscriber before the slowest ones start missing some.
+pub const CHANGE_FEED_CAPACITY: usize = 256;
+
+/// Answers requests for paths that no route matches, echoing the path in the error body.
+async fn not_found(uri: Uri) -> ApiError {
+    ApiError::not_found("not found").with_path(uri.path())
+}
+
+/// Builds the `MethodRouter` of a route and remembers the methods it serves, so `finish` can answer the others
+/// with a JSON `405` listing them in `Allow`. axum sets that header itself, but 0.5 loses it as soon as the route
+/// has middleware.
+struct Methods<B> {
+    router: MethodRouter<B>,
+    allowed: Vec<&'static str>,
+}
+
+fn methods<B: Send + 'static>() -> Methods<B> {
+    Methods { router: MethodRouter::new(), allowed: Vec::new() }
+}
+
+impl<B: Send + 'static> Methods<B> {
+    /// Serves `GET` and, like axum does, `HEAD` too.
+    fn get<H: Handler<T, B>, T: 'static>(self, handler: H) -> Self {
+        Methods { router: self.router.get(handler), ..self }.allowing(&["GET", "HEAD"])
+    }
+
+    fn head<H: Handler<T, B>, T: 'static>(self, handler: H) -> Self {
+        Methods { router: self.router.head(handler), ..self }.allowing(&["HEAD"])
+    }
+
+    fn post<H: Handler<T, B>, T: 'static>(self, handler: H) -> Self {
+        Methods { router: self.router.post(handler), ..self }.allowing(&["POST"])
+    }
+
+    fn put<H: Handler<T, B>, T: 'static>(self, handler: H) -> Self {
+        Methods { router: self.router.put(handler), ..self }.allowing(&["PUT"])
+    }
+
+    fn patch<H: Handler<T, B>, T: 'static>(self, handler: H) -> Self {
+        Methods { router: self.router.patch(handler), ..self }.allowing(&["PATCH"])
+    }
+
+    fn delete<H: Handler<T, B>, T: 'static>(self, handler: H) -> Self {
+        Methods { router: self.router.delete(handler), ..self }.allowing(&["DELETE"])
+    }
+
+    fn allowing(mut self, methods: &[&'static str]) -> Self {
+        for method in methods {
+            if !self.allowed.contains(method) {
+                self.allowed.push(method);
+            }
+        }
+        self
+    }
+
+    /// The router, answering the methods it doesn't serve with a `405`.
+    fn finish(self) -> MethodRouter<B> {
+        let allow = self.allowed.join(",");
+        self.router.fallback((move || {
+            let allow = allow.clone();
+            async move { ([(header::ALLOW, allow)], ApiError::new(StatusCode::METHOD_NOT_ALLOWED, "method not allowed")) }
+        }).into_service())
+    }
+}
+
+/// Serves the recorded metrics in the Prometheus text format, sampling the pool gauges first.
+async fn metrics_handler(Extension(state): Extension<AppState>) -> impl IntoResponse {
+    let size = state.pool.size();
+    let idle = state.pool.num_idle() as u32;
+    metrics::gauge!("db_pool_size").set(size);
+    metrics::gauge!("db_pool_connections_idle").set(idle);
+    metrics::gauge!("db_pool_connections_active").set(size.saturating_sub(idle));
+
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], state.metrics.render())
+}
+
+#[utoipa::path(
+    get,
+    path = "/entries",
+    params(Pagination, EntryFilter, Sorting, FieldSelection),
+    responses(
+        (status = 200, description = "One page of entries, or every matching entry as newline-delimited JSON when `Accept: application/x-ndjson` is sent", body = EntryPage, headers(
+            ("x-total-count" = i64, description = "Total number of matching entries"),
+            ("last-modified" = String, description = "Latest creation or update of the entries on the page, or deletion of any entry"),
+            ("link" = String, description = "The first, previous, next and last pages, unless paging with a cursor"),
+        )),
+        (status = 304, description = "None of the entries on the page changed since If-Modified-Since"),
+        (status = 400, description = "Malformed pagination, or an invalid sort key or cursor"),
+        (status = 422, description = "A per_page below 1, the details name the parameter"),
+        (status = 403, description = "include_deleted was asked for without an admin token"),
+    )
+)]
+// every extractor is an argument, there is no splitting this up
+#[allow(clippy::too_many_arguments)]
+async fn get_blogs(Extension(state): Extension<AppState>, user: Option<AuthUser>, OriginalUri(uri): OriginalUri, headers: HeaderMap, ValidatedQuery(pagination): ValidatedQuery<Pagination>, Query(filter): Query<EntryFilter>, Query(sorting): Query<Sorting>, Query(selection): Query<FieldSelection>) -> Result<Response, ApiError> {
+    check_filter(&filter, user)?;
+    let fields = selection.resolve()?;
+    let columns = fields.as_ref().map_or_else(|| ENTRY_COLUMNS.to_owned(), Fields::columns);
+    let (page, per_page) = pagination.resolve(state.config.max_page_size);
+    let cursor = pagination.cursor()?;
+    let order_by = sorting.order_by()?;
+    if cursor.is_some() && !sorting.newest_first() {
+        return Err(ApiError::bad_request("after only works with the default -created sort"));
+    }
+    let (where_clause, next_param) = filter.where_clause();
+
+    if accepts_ndjson(&headers) {
+        let select_sql = format!("select {} from blog_entry{} order by {}", columns, where_clause, order_by);
+        let body = StreamBody::new(ndjson_entries(state.read_pool, filter, fields, select_sql));
+        return Ok(([(header::CONTENT_TYPE, NDJSON)], body).into_response());
+    }
+
+    let count_sql = format!("select count(*) from blog_entry{}", where_clause);
+    let (total,): (i64,) = state.config.retry
+        .run(|| filter.bind(sqlx::query_as(&count_sql)).fetch_one(&state.read_pool))
+        .await
+        .map_err(internal_error)?;
+
+    // the cursor only narrows the page, the total still counts every matching entry
+    let (select_where, next_param) = match cursor {
+        Some(_) => {
+            let keyword = if where_clause.is_empty() { " where" } else { " and" };
+            (format!("{}{} (created, id) < (${}, ${})", where_clause, keyword, next_param, next_param + 1), next_param + 2)
+        }
+        None => (where_clause, next_param),
+    };
+    let offset = if cursor.is_some() { 0 } else { page as i64 * per_page as i64 };
+    let select_sql = format!(
+        "select {} from blog_entry{} order by {} limit ${} offset ${}",
+        columns, select_where, order_by, next_param, next_param + 1
+    );
+    let entries: Vec<BlogEntry> = state.config.retry
+        .run(|| {
+            let mut query = filter.bind(sqlx::query_as(&select_sql));
+            if let Some(cursor) = cursor {
+                query = query.bind(cursor.created).bind(cursor.id);
+            }
+            query.bind(per_page as i64).bind(offset).fetch_all(&state.read_pool)
+        })
+        .await
+        .map_err(internal_error)?;
+    let next_cursor = match entries.last() {
+        Some(BlogEntry { id: Some(id), created, .. }) if sorting.newest_first() && entries.len() == per_page as usize => {
+            Some(Cursor { created: *created, id: *id }.encode())
+        }
+        _ => None,
+    };
+
+    // a deleted entry leaves the page without a trace among the ones still on it, the latest deletion counts too
+    let (last_deleted,): (Option<DateTime<Utc>>,) = sqlx::query_as("select max(deleted_at) from blog_entry")
+        .fetch_one(&state.read_pool)
+        .await
+        .map_err(internal_error)?;
+    let cache_control = format!("max-age={}", state.config.cache_max_age.as_secs());
+    let last_modified = match entries.iter().map(|entry| entry.updated.unwrap_or(entry.created)).chain(last_deleted).max() {
+        Some(last_modified) if !modified_since(&headers, last_modified) => {
+            let headers = [(header::CACHE_CONTROL, cache_control), (header::LAST_MODIFIED, http_date(last_modified))];
+            return Ok((StatusCode::NOT_MODIFIED, headers).into_response());
+        }
+        Some(last_modified) => Some(http_date(last_modified)),
+        None => None,
+    };
+
+    // pages are numbered for offsets only, a cursor has its next_cursor instead
+    let links = if cursor.is_none() { Some(page_links(&uri, page, per_page, total)) } else { None };
+
+    let headers = [(X_TOTAL_COUNT, total.to_string()), (header::CACHE_CONTROL, cache_control)];
+    let entries = entries.into_iter().map(BlogEntry::with_reading_time);
+    let mut response = match fields {
+        Some(fields) => {
+            let items = entries.map(|entry| fields.project(&entry)).collect();
+            (headers, Json(Page { items, page, per_page, total, next_cursor })).into_response()
+        }
+        None => (headers, Json(Page { items: entries.collect(), page, per_page, total, next_cursor })).into_response(),
+    };
+    if let Some(last_modified) = last_modified {
+        response.headers_mut().insert(header::LAST_MODIFIED, HeaderValue::from_str(&last_modified).expect("http dates are valid header values"));
+    }
+    if let Some(links) = links {
+        response.headers_mut().insert(header::LINK, HeaderValue::from_str(&links).expect("urls are valid header values"));
+    }
+    Ok(response)
+}
+
+/// Renders the `Link` header pointing at the first, previous, next and last pages, as in RFC 5988.
+/// The links are the requested url with only its `page` changed; `prev` and `next` are left out at the ends.
+fn page_links(uri: &Uri, page: u32, per_page: u32, total: i64) -> String {
+    let last = ((total.max(1) - 1) / per_page as i64) as u32;
+    let link = |page: u32, rel: &str| {
+        let query = url::form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(url::form_urlencoded::parse(uri.query().unwrap_or_default().as_bytes()).filter(|(key, _)| key != "page"))
+            .append_pair("page", &page.to_string())
+            .finish();
+        format!("<{}?{}>; rel=\"{}\"", uri.path(), query, rel)
+    };
+
+    let mut links = vec![link(0, "first")];
+    if page > 0 {
+        links.push(link((page - 1).min(last), "prev"));
+    }
+    if page < last {
+        links.push(link(page + 1, "next"));
+    }
+    links.push(link(last, "last"));
+    links.join(", ")
+}
+
+/// Whether `last_modified` is later than `If-Modified-Since`, to the second, or there is no such header.
+/// An unparsable date is ignored, like a missing one.
+fn modified_since(headers: &HeaderMap, last_modified: DateTime<Utc>) -> bool {
+    headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| DateTime::parse_from_rfc2822(value).ok())
+        .is_none_or(|since| last_modified.timestamp() > since.timestamp())
+}
+
+/// Formats `time` as an HTTP date, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`.
+fn http_date(time: DateTime<Utc>) -> String {
+    time.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+#[utoipa::path(
+    head,
+    path = "/entries",
+    params(EntryFilter),
+    responses(
+        (status = 200, description = "The number of matching entries, without the entries themselves", headers(("x-total-count" = i64, description = "Total number of matching entries"))),
+        (status = 400, description = "Invalid filter"),
+        (status = 403, description = "include_deleted was asked for without an admin token"),
+    )
+)]
+async fn count_blogs(Extension(state): Extension<AppState>, user: Option<AuthUser>, Query(filter): Query<EntryFilter>) -> Result<impl IntoResponse, ApiError> {
+    check_filter(&filter, user)?;
+    let (where_clause, _) = filter.where_clause();
+    let count_sql = format!("select count(*) from blog_entry{}", where_clause);
+    let (total,): (i64,) = state.config.retry
+        .run(|| filter.bind(sqlx::query_as(&count_sql)).fetch_one(&state.read_pool))
+        .await
+        .map_err(internal_error)?;
+
+    Ok([(X_TOTAL_COUNT, total.to_string())])
+}
+
+/// Rejects invalid filters, and deleted entries for anyone but admins.
+fn check_filter(filter: &EntryFilter, user: Option<AuthUser>) -> Result<(), ApiError> {
+    if filter.include_deleted && !user.map(|user| user.admin).unwrap_or(false) {
+        return Err(ApiError::new(StatusCode::FORBIDDEN, "include_deleted requires an admin token"));
+    }
+    filter.validate()
+}
+
+/// Media type of newline-delimited JSON, one entry per line.
+const NDJSON: &str = "application/x-ndjson";
+
+/// Whether the client asked for the list as newline-delimited JSON rather than a page.
+fn accepts_ndjson(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|media_type| media_type.split(';').next().map(str::trim) == Some(NDJSON))
+}
+
+/// Streams every entry matching `filter` as a line of JSON, without loading them all into memory.
+/// Pagination does not apply, the stream is meant for exports.
+fn ndjson_entries(pool: PgPool, filter: EntryFilter, fields: Option<Fields>, select_sql: String) -> impl Stream<Item = Result<Vec<u8>, BoxError>> {
+    async_stream::try_stream! {
+        let mut entries = filter.bind(sqlx::query_as::<_, BlogEntry>(&select_sql)).fetch(&pool);
+        while let Some(entry) = entries.try_next().await.map_err(|err| {
+            warn!("streaming entries failed: {}", err);
+            BoxError::from(err)
+        })? {
+            let entry = entry.with_reading_time();
+            let mut line = match &fields {
+                Some(fields) => serde_json::to_vec(&fields.project(&entry))?,
+                None => serde_json::to_vec(&entry)?,
+            };
+            line.push(b'\n');
+            yield line;
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/entries.csv",
+    responses(
+        (status = 200, description = "Every entry as CSV with a `created,title,author,text` header row", content_type = "text/csv"),
+    )
+)]
+async fn export_csv(Extension(state): Extension<AppState>) -> impl IntoResponse {
+    (
+        [
+            (header::CONTENT_TYPE, "text/csv"),
+            (header::CONTENT_DISPOSITION, "attachment; filename=\"entries.csv\""),
+        ],
+        StreamBody::new(csv_entries(state.read_pool)),
+    )
+}
+
+/// Streams all entries as CSV rows, oldest first, after a header row.
+/// Quoting of commas, quotes and newlines in the values is left to the csv writer.
+fn csv_entries(pool: PgPool) -> impl Stream<Item = Result<Vec<u8>, BoxError>> {
+    async_stream::try_stream! {
+        yield csv_row(["created", "title", "author", "text"])?;
+
+        let select_sql = format!("select {} from blog_entry where deleted_at is null order by created, id", ENTRY_COLUMNS);
+        let mut entries = sqlx::query_as::<_, BlogEntry>(&select_sql).fetch(&pool);
+        while let Some(entry) = entries.try_next().await.map_err(|err| {
+            warn!("exporting entries failed: {}", err);
+            BoxError::from(err)
+        })? {
+            yield csv_row([entry.created.to_rfc3339(), entry.title, entry.author, entry.text])?;
+        }
+    }
+}
+
+/// Renders one CSV record, terminated by a newline.
+fn csv_row<I, T>(record: I) -> Result<Vec<u8>, BoxError>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<[u8]>,
+{
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(record)?;
+    Ok(writer.into_inner().map_err(|err| err.into_error())?)
+}
+
+#[utoipa::path(
+    get,
+    path = "/entries/{id}",
+    params(("id" = Uuid, Path, description = "Id of the entry")),
+    responses(
+        (status = 200, description = "The entry", body = BlogEntry, headers(("etag" = String, description = "Version of the entry, for If-None-Match"))),
+        (status = 304, description = "The entry still matches the ETag in If-None-Match"),
+        (status = 404, description = "No entry with this id"),
+    )
+)]
+async fn get_blog(Extension(state): Extension<AppState>, Path(id): Path<Uuid>, headers: HeaderMap) -> Result<Response, ApiError> {
+    let entry: BlogEntry = sqlx::query_as(&format!("select {} from blog_entry where id = $1 and deleted_at is null", ENTRY_COLUMNS))
+        .bind(id)
+        .fetch_optional(&state.read_pool)
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(|| ApiError::not_found(format!("blog entry {} not found", id)))?;
+    Ok(entry_response(entry, &headers))
+}
+
+#[utoipa::path(
+    get,
+    path = "/entries/by-slug/{slug}",
+    params(("slug" = String, Path, description = "Slug of the entry, as the entry lists it")),
+    responses(
+        (status = 200, description = "The entry", body = BlogEntry, headers(("etag" = String, description = "Version of the entry, for If-None-Match"))),
+        (status = 304, description = "The entry still matches the ETag in If-None-Match"),
+        (status = 404, description = "No entry with this slug"),
+    )
+)]
+async fn get_blog_by_slug(Extension(state): Extension<AppState>, Path(slug): Path<String>, headers: HeaderMap) -> Result<Response, ApiError> {
+    let entry: BlogEntry = sqlx::query_as(&format!("select {} from blog_entry where slug = $1 and deleted_at is null", ENTRY_COLUMNS))
+        .bind(&slug)
+        .fetch_optional(&state.read_pool)
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(|| ApiError::not_found(format!("no blog entry with slug {:?}", slug)))?;
+    Ok(entry_response(entry, &headers))
+}
+
+#[utoipa::path(
+    get,
+    path = "/entries/latest",
+    responses(
+        (status = 200, description = "The most recently created entry", body = BlogEntry),
+        (status = 404, description = "There are no entries"),
+    )
+)]
+async fn latest_blog(Extension(state): Extension<AppState>) -> Result<Json<BlogEntry>, ApiError> {
+    let entry: BlogEntry = sqlx::query_as(&format!("select {} from blog_entry where deleted_at is null order by created desc, id desc limit 1", ENTRY_COLUMNS))
+        .fetch_optional(&state.read_pool)
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(|| ApiError::not_found("there are no blog entries"))?;
+    Ok(Json(entry.with_reading_time()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/entries/random",
+    responses(
+        (status = 200, description = "An entry picked at random", body = BlogEntry),
+        (status = 404, description = "There are no entries"),
+    )
+)]
+async fn random_blog(Extension(state): Extension<AppState>) -> Result<Json<BlogEntry>, ApiError> {
+    // order by random() reads and sorts every entry, fine for a blog; with millions of rows,
+    // tablesample system picks from a few pages instead, at the cost of not being uniform
+    let entry: BlogEntry = sqlx::query_as(&format!("select {} from blog_entry where deleted_at is null order by random() limit 1", ENTRY_COLUMNS))
+        .fetch_optional(&state.read_pool)
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(|| ApiError::not_found("there are no blog entries"))?;
+    Ok(Json(entry.with_reading_time()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/entries/{id}/html",
+    params(("id" = Uuid, Path, description = "Id of the entry")),
+    responses(
+        (status = 200, description = "The text of the entry rendered from markdown, without unsafe markup", body = String, content_type = "text/html"),
+        (status = 404, description = "No entry with this id"),
+    )
+)]
+async fn get_blog_html(Extension(state): Extension<AppState>, Path(id): Path<Uuid>) -> Result<Html<String>, ApiError> {
+    let (text,): (String,) = sqlx::query_as("select text from blog_entry where id = $1 and deleted_at is null")
+        .bind(id)
+        .fetch_optional(&state.read_pool)
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(|| ApiError::not_found(format!("blog entry {} not found", id)))?;
+    Ok(Html(render_markdown(&text)))
+}
+
+/// The entry with its ETag, or `304 Not Modified` when the client's copy is still current.
+fn entry_response(entry: BlogEntry, headers: &HeaderMap) -> Response {
+    let entry = entry.with_reading_time();
+    let etag = match entry.etag() {
+        Some(etag) => etag,
+        None => return Json(entry).into_response(),
+    };
+    if if_none_match(headers, &etag) {
+        return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response();
+    }
+    ([(header::ETAG, etag)], Json(entry)).into_response()
+}
+
+/// Whether `If-None-Match` lists `etag` or is `*`, i.e. the client's copy is still current.
+/// Compares weakly, as a `W/` prefix makes no difference for a `GET`.
+fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+#[utoipa::path(
+    get,
+    path = "/entries/search",
+    params(SearchParams),
+    responses(
+        (status = 200, description = "Matching entries, best match first", body = [BlogEntry]),
+        (status = 400, description = "Empty search query"),
+    )
+)]
+async fn search_blogs(Extension(state): Extension<AppState>, Query(params): Query<SearchParams>) -> Result<Json<Vec<BlogEntry>>, ApiError> {
+    if params.q.trim().is_empty() {
+        return Err(ApiError::bad_request("q must not be empty"));
+    }
+
+    // the tsvector expression must match the one in the blog_entry_search index for it to be used
+    let sql = format!(
+        "select {} from blog_entry \
+         where deleted_at is null and to_tsvector('english', coalesce(title, '') || ' ' || coalesce(text, '')) @@ plainto_tsquery('english', $1) \
+         order by ts_rank(to_tsvector('english', coalesce(title, '') || ' ' || coalesce(text, '')), plainto_tsquery('english', $1)) desc",
+        ENTRY_COLUMNS
+    );
+    let entries: Vec<BlogEntry> = sqlx::query_as(&sql)
+        .bind(params.q)
+        .fetch_all(&state.read_pool)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(entries.into_iter().map(BlogEntry::with_reading_time).collect()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/entries",
+    params(("idempotency-key" = Option<String>, Header, description = "Sending the same key again returns the entry created the first time instead of a new one")),
+    request_body = NewBlogEntry,
+    responses(
+        (status = 201, description = "The new entry", body = BlogEntry, headers(("location" = String, description = "Where the new entry can be fetched"))),
+        (status = 400, description = "Malformed entry or Idempotency-Key"),
+        (status = 422, description = "Invalid entry, the details name the fields that failed, or an Idempotency-Key already used for a different entry"),
+        (status = 401, description = "Missing or invalid X-Api-Key or bearer token, both are required"),
+        (status = 409, description = "Entry conflicts with an existing one"),
+        (status = 410, description = "The entry created earlier with the same Idempotency-Key has been deleted since"),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+async fn add_blog(Extension(state): Extension<AppState>, user: AuthUser, headers: HeaderMap, ValidatedJson(blog): ValidatedJson<NewBlogEntry>) -> Result<Response, ApiError> {
+    check_author(&user)?;
+
+    let idempotency_key = idempotency_key(&headers)?;
+    let request = serde_json::json!({ "title": &blog.title, "text": &blog.text, "tags": &blog.tags }).to_string();
+    if let Some(key) = idempotency_key {
+        if let Some(id) = replayed_entry(&state.pool, &user.sub, key, &request).await? {
+            return created_entry(&state, id).await;
+        }
+    }
+
+    // Postgres keeps microseconds, so does the entry sent back
+    let now = Utc::now().trunc_subsecs(6);
+    // an insert is not idempotent, so it is retried at most once; a failed attempt is rolled back
+    // when its transaction is dropped
+    let inserted = state.config.retry
+        .at_most_once()
+        .run(|| async {
+            let mut tx = state.pool.begin().await?;
+            let slug = unique_slug(&mut tx, &blog.title, &[]).await?;
+            let (id,): (Uuid,) = sqlx::query_as("insert into blog_entry (created, updated, title, author, text, slug) values ($1, $2, $3, $4, $5, $6) returning id")
+                .bind(now)
+                .bind(now)
+                .bind(&blog.title)
+                .bind(&user.sub)
+                .bind(&blog.text)
+                .bind(&slug)
+                .fetch_one(&mut tx)
+                .await?;
+            tag_entry(&mut tx, id, &blog.tags).await?;
+            if let Some(key) = idempotency_key {
+                // an expired key is taken over, a live one means a concurrent request with the same key won
+                let claimed = sqlx::query(
+                    "insert into idempotency_key (author, key, request, entry_id, created) values ($1, $2, $3, $4, $5) \
+                     on conflict (author, key) do update set request = excluded.request, entry_id = excluded.entry_id, created = excluded.created \
+                     where idempotency_key.created <= now() - make_interval(secs => $6)",
+                )
+                    .bind(&user.sub)
+                    .bind(key)
+                    .bind(&request)
+                    .bind(id)
+                    .bind(now)
+                    .bind(IDEMPOTENCY_WINDOW.as_secs_f64())
+                    .execute(&mut tx)
+                    .await?;
+                if claimed.rows_affected() == 0 {
+                    return Ok(None);
+                }
+            }
+            notify_changed(&mut tx, id).await?;
+            tx.commit().await?;
+            Ok(Some((id, slug)))
+        })
+        .await
+        .map_err(map_db_error)?;
+    let (id, slug) = match (inserted, idempotency_key) {
+        (Some(inserted), _) => inserted,
+        (None, Some(key)) => {
+            return match replayed_entry(&state.pool, &user.sub, key, &request).await? {
+                Some(id) => created_entry(&state, id).await,
+                None => Err(ApiError::new(StatusCode::CONFLICT, "a concurrent request with the same Idempotency-Key is still in progress")),
+            };
+        }
+        (None, None) => unreachable!("only a claimed idempotency key can stop the insert"),
+    };
+
+    let entry = BlogEntry {
+        id: Some(id),
+        created: now,
+        updated: Some(now),
+        title: blog.title,
+        author: user.sub,
+        text: blog.text,
+        tags: blog.tags,
+        deleted_at: None,
+        version: Some(1),
+        slug: Some(slug),
+        reading_time_minutes: None,
+    }
+    .with_reading_time();
+    // nobody listening is fine
+    let _ = state.created.send(entry.clone());
+    Ok(created(&state, entry))
+}
+
+#[utoipa::path(
+    post,
+    path = "/entries/validate",
+    request_body = NewBlogEntry,
+    responses(
+        (status = 204, description = "The entry would be accepted by `POST /entries`, nothing was stored"),
+        (status = 400, description = "Malformed entry"),
+        (status = 422, description = "Invalid entry, the details name the fields that failed"),
+    ),
+    security(("api_key" = []))
+)]
+async fn validate_blog(ValidatedJson(_blog): ValidatedJson<NewBlogEntry>) -> StatusCode {
+    // the extractor already rejected anything POST /entries would reject
+    StatusCode::NO_CONTENT
+}
+
+/// Answers a replayed `POST /entries` like the original one, with the entry as it is now,
+/// or `410 Gone` once that entry has been deleted.
+async fn created_entry(state: &AppState, id: Uuid) -> Result<Response, ApiError> {
+    let entry: BlogEntry = sqlx::query_as(&format!("select {} from blog_entry where id = $1 and deleted_at is null", ENTRY_COLUMNS))
+        .bind(id)
+        .fetch_optional(&state.pool)
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(|| ApiError::new(StatusCode::GONE, "the entry created with this Idempotency-Key has been deleted"))?;
+    Ok(created(state, entry))
+}
+
+/// `201 Created` with the entry and where to find it.
+fn created(state: &AppState, entry: BlogEntry) -> Response {
+    let prefix = state.config.api_prefix.as_deref().unwrap_or_default();
+    let location = format!("{}/entries/{}", prefix, entry.id.map(|id| id.to_string()).unwrap_or_default());
+    (StatusCode::CREATED, [(header::LOCATION, location)], Json(entry.with_reading_time())).into_response()
+}
+
+/// `slugify(title)`, with the lowest counter from 2 up appended that no other entry has yet, e.g. `hello-world-2`.
+/// `reserved` are the slugs picked for entries that are about to be inserted along with this one.
+/// Two entries with the same title created at the same time can still pick the same one, the unique constraint
+/// turns the second into a conflict.
+async fn unique_slug(tx: &mut Transaction<'_, Postgres>, title: &str, reserved: &[String]) -> Result<String, sqlx::Error> {
+    let slug = slugify(title);
+    // a slug has only letters, digits and hyphens, none of them special to like
+    let stored: Vec<(String,)> = sqlx::query_as("select slug from blog_entry where slug = $1 or slug like $1 || '-%'")
+        .bind(&slug)
+        .fetch_all(&mut *tx)
+        .await?;
+    let taken = |candidate: &str| stored.iter().any(|(taken,)| taken == candidate) || reserved.iter().any(|taken| taken == candidate);
+    if !taken(&slug) {
+        return Ok(slug);
+    }
+    let candidate = (2..)
+        .map(|counter| format!("{}-{}", slug, counter))
+        .find(|candidate| !taken(candidate))
+        .expect("the counters never run out");
+    Ok(candidate)
+}
+
+/// Attaches `tags` to entry `id`, creating the tags that don't exist yet.
+async fn tag_entry(tx: &mut Transaction<'_, Postgres>, id: Uuid, tags: &[String]) -> Result<(), sqlx::Error> {
+    if tags.is_empty() {
+        return Ok(());
+    }
+    sqlx::query("insert into tags (name) select unnest($1::text[]) on conflict (name) do nothing")
+        .bind(tags)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("insert into blog_entry_tags (blog_entry_id, tag_id) select $1, id from tags where name = any($2)")
+        .bind(id)
+        .bind(tags)
+        .execute(&mut *tx)
+        .await?;
+    Ok(())
+}
+
+/// The author is whoever signed in, the claim gets the same check the field used to get.
+fn check_author(user: &AuthUser) -> Result<(), ApiError> {
+    if !validator::validate_email(&user.sub) {
+        let mut errors = validator::ValidationErrors::new();
+        let mut error = validator::ValidationError::new("email");
+        error.message = Some("author must be a valid email address".into());
+        errors.add("author", error);
+        return Err(ServerError::ValidationError(errors).into());
+    }
+    Ok(())
+}
+
+/// Most entries accepted in one bulk insert. They are all inserted in one transaction, so this bounds the size
+/// of the request and how long that transaction holds its locks.
+const MAX_BULK_ENTRIES: usize = 1000;
+
+/// Header that makes retrying `POST /entries` safe.
+const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+
+/// How long an `Idempotency-Key` is remembered, a later request with the same key creates a new entry.
+const IDEMPOTENCY_WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The `Idempotency-Key` sent, if any; it must be between 1 and 255 visible characters.
+fn idempotency_key(headers: &HeaderMap) -> Result<Option<&str>, ApiError> {
+    match headers.get(IDEMPOTENCY_KEY) {
+        Some(value) => match value.to_str() {
+            Ok(key) if !key.is_empty() && key.len() <= 255 => Ok(Some(key)),
+            _ => Err(ApiError::bad_request("Idempotency-Key must be between 1 and 255 visible characters")),
+        },
+        None => Ok(None),
+    }
+}
+
+/// The entry `author` created with `key` within the `IDEMPOTENCY_WINDOW`. Reusing the key for a different request is an error.
+async fn replayed_entry(pool: &PgPool, author: &str, key: &str, request: &str) -> Result<Option<Uuid>, ApiError> {
+    let previous: Option<(String, Uuid)> = sqlx::query_as(
+        "select request, entry_id from idempotency_key \
+         where author = $1 and key = $2 and created > now() - make_interval(secs => $3)",
+    )
+        .bind(author)
+        .bind(key)
+        .bind(IDEMPOTENCY_WINDOW.as_secs_f64())
+        .fetch_optional(pool)
+        .await
+        .map_err(internal_error)?;
+    match previous {
+        Some((previous, id)) if previous == request => Ok(Some(id)),
+        Some(_) => Err(ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, "Idempotency-Key was already used for a different entry")),
+        None => Ok(None),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/entries/bulk",
+    request_body = [NewBlogEntry],
+    responses(
+        (status = 200, description = "All entries were inserted", body = BulkInserted),
+        (status = 400, description = "Malformed body, or no or too many entries"),
+        (status = 422, description = "Invalid entries, the details name the index that failed, e.g. `entries[2].title`"),
+        (status = 401, description = "Missing or invalid X-Api-Key or bearer token, both are required"),
+        (status = 409, description = "An entry conflicts with an existing one, nothing was inserted"),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+async fn add_blogs_bulk(Extension(state): Extension<AppState>, user: AuthUser, ValidatedJson(blogs): ValidatedJson<NewBlogEntries>) -> Result<Json<BulkInserted>, ApiError> {
+    check_author(&user)?;
+    if blogs.entries.is_empty() {
+        return Err(ApiError::bad_request("at least one entry must be provided"));
+    }
+    if blogs.entries.len() > MAX_BULK_ENTRIES {
+        return Err(ApiError::bad_request(format!("at most {} entries can be inserted at once", MAX_BULK_ENTRIES)));
+    }
+
+    let mut tx = state.pool.begin().await.map_err(internal_error)?;
+    let result = insert_entries(&mut tx, &blogs.entries, &user.sub).await;
+    let inserted = finish_transaction(tx, result).await?;
+
+    Ok(Json(BulkInserted { inserted }))
+}
+
+/// Rows per insert statement in a bulk insert.
+const BULK_CHUNK_SIZE: usize = 100;
+
+/// Inserts `entries` by `author` in multi-row statements of `BULK_CHUNK_SIZE` rows, returning how many were inserted.
+/// The statements run in `tx`, so a failing chunk must roll back the ones before it.
+async fn insert_entries(tx: &mut Transaction<'_, Postgres>, entries: &[NewBlogEntry], author: &str) -> Result<u64, ApiError> {
+    let now = Utc::now();
+    let mut inserted = 0;
+    for chunk in entries.chunks(BULK_CHUNK_SIZE) {
+        // the earlier chunks are already in the table, only the slugs of this one need reserving
+        let mut slugs: Vec<String> = Vec::with_capacity(chunk.len());
+        for blog in chunk {
+            let slug = unique_slug(tx, &blog.title, &slugs).await.map_err(map_db_error)?;
+            slugs.push(slug);
+        }
+
+        // six parameters per row
+        let rows: Vec<String> = (0..chunk.len())
+            .map(|row| {
+                let first = row * 6 + 1;
+                format!("(${}, ${}, ${}, ${}, ${}, ${})", first, first + 1, first + 2, first + 3, first + 4, first + 5)
+            })
+            .collect();
+        let sql = format!("insert into blog_entry (created, updated, title, author, text, slug) values {} returning id", rows.join(", "));
+
+        let mut query = sqlx::query_as(&sql);
+        for (blog, slug) in chunk.iter().zip(&slugs) {
+            query = query.bind(now).bind(now).bind(&blog.title).bind(author).bind(&blog.text).bind(slug);
+        }
+        // the ids come back in the order of the values list
+        let ids: Vec<(Uuid,)> = query.fetch_all(&mut *tx).await.map_err(map_db_error)?;
+        for ((id,), blog) in ids.iter().zip(chunk) {
+            tag_entry(tx, *id, &blog.tags).await.map_err(map_db_error)?;
+            notify_changed(tx, *id).await.map_err(map_db_error)?;
+        }
+        inserted += ids.len() as u64;
+    }
+    Ok(inserted)
+}
+
+/// Commits `tx` when `result` is ok and rolls it back otherwise, so an operation failing half way
+/// leaves nothing behind. The error of the operation is passed on, a failed rollback is only logged.
+async fn finish_transaction<T>(tx: Transaction<'_, Postgres>, result: Result<T, ApiError>) -> Result<T, ApiError> {
+    match result {
+        Ok(value) => {
+            tx.commit().await.map_err(internal_error)?;
+            Ok(value)
+        }
+        Err(err) => {
+            if let Err(rollback_err) = tx.rollback().await {
+                warn!("rolling back transaction failed: {}", rollback_err);
+            }
+            Err(err)
+        }
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/entries/{id}",
+    params(
+        ("id" = Uuid, Path, description = "Id of the entry"),
+        ("if-match" = Option<String>, Header, description = "ETag or version the entry must still have, e.g. `\"3\"`"),
+    ),
+    request_body = BlogEntry,
+    responses(
+        (status = 200, description = "Entry replaced", body = Versioned),
+        (status = 400, description = "Malformed entry"),
+        (status = 422, description = "Invalid entry, the details name the fields that failed"),
+        (status = 401, description = "Missing or invalid X-Api-Key"),
+        (status = 404, description = "No entry with this id"),
+        (status = 409, description = "The entry was changed since the given version"),
+    ),
+    security(("api_key" = []))
+)]
+async fn update_blog(
+    Extension(state): Extension<AppState>,
+    Path(id): Path<Uuid>,
+    headers: HeaderMap,
+    ValidatedJson(blog): ValidatedJson<BlogEntry>,
+) -> Result<Json<Versioned>, ApiError> {
+    let expected = match if_match_version(&headers, id)? {
+        Some(version) => Some(version),
+        None => blog.version,
+    };
+    // like every other change to an entry, the database sets updated
+    let query = sqlx::query_as("update blog_entry set created = $1, updated = now(), title = $2, author = $3, text = $4, version = version + 1 \
+            where id = $5 and deleted_at is null and ($6::integer is null or version = $6) returning version")
+        .bind(blog.created)
+        .bind(blog.title)
+        .bind(blog.author)
+        .bind(blog.text)
+        .bind(id)
+        .bind(expected);
+
+    let mut tx = state.pool.begin().await.map_err(internal_error)?;
+    let result = async {
+        let updated: Option<(i32,)> = query.fetch_optional(&mut tx).await.map_err(map_db_error)?;
+        let (version,) = match updated {
+            Some(updated) => updated,
+            None => return Err(not_updated(&mut tx, id).await),
+        };
+        notify_changed(&mut tx, id).await.map_err(internal_error)?;
+        Ok(Versioned { version })
+    }.await;
+    finish_transaction(tx, result).await.map(Json)
+}
+
+#[utoipa::path(
+    patch,
+    path = "/entries/{id}",
+    params(
+        ("id" = Uuid, Path, description = "Id of the entry"),
+        ("if-match" = Option<String>, Header, description = "ETag or version the entry must still have, e.g. `\"3\"`"),
+    ),
+    request_body = BlogEntryPatch,
+    responses(
+        (status = 200, description = "Entry updated", body = Versioned),
+        (status = 400, description = "Malformed patch, or no fields given"),
+        (status = 422, description = "Invalid patch"),
+        (status = 401, description = "Missing or invalid X-Api-Key"),
+        (status = 404, description = "No entry with this id"),
+        (status = 409, description = "The entry was changed since the given version"),
+    ),
+    security(("api_key" = []))
+)]
+async fn patch_blog(
+    Extension(state): Extension<AppState>,
+    Path(id): Path<Uuid>,
+    headers: HeaderMap,
+    ValidatedJson(patch): ValidatedJson<BlogEntryPatch>,
+) -> Result<Json<Versioned>, ApiError> {
+    let fields: Vec<(&str, &String)> = [("title", &patch.title), ("author", &patch.author), ("text", &patch.text)]
+        .into_iter()
+        .filter_map(|(column, value)| value.as_ref().map(|value| (column, value)))
+        .collect();
+    if fields.is_empty() {
+        return Err(ApiError::bad_request("at least one of title, author or text must be provided"));
+    }
+    let expected = match if_match_version(&headers, id)? {
+        Some(version) => Some(version),
+        None => patch.version,
+    };
+
+    // only the hard-coded column names end up in the sql, the values are bound
+    let assignments: Vec<String> = fields
+        .iter()
+        .enumerate()
+        .map(|(index, (column, _))| format!("{} = ${}", column, index + 1))
+        .collect();
+    let id_param = fields.len() + 1;
+    let sql = format!(
+        "update blog_entry set updated = now(), {}, version = version + 1 where id = ${} and deleted_at is null and (${}::integer is null or version = ${}) returning version",
+        assignments.join(", "),
+        id_param,
+        id_param + 1,
+        id_param + 1
+    );
+
+    let mut query = sqlx::query_as(&sql);
+    for (_, value) in fields {
+        query = query.bind(value);
+    }
+    let query = query.bind(id).bind(expected);
+
+    let mut tx = state.pool.begin().await.map_err(internal_error)?;
+    let result = async {
+        let updated: Option<(i32,)> = query.fetch_optional(&mut tx).await.map_err(map_db_error)?;
+        let (version,) = match updated {
+            Some(updated) => updated,
+            None => return Err(not_updated(&mut tx, id).await),
+        };
+        notify_changed(&mut tx, id).await.map_err(internal_error)?;
+        Ok(Versioned { version })
+    }.await;
+    finish_transaction(tx, result).await.map(Json)
+}
+
+/// The version a write to entry `id` must match, taken from `If-Match`: the entry's ETag or a bare version
+/// (`"3"`, quotes optional). `None` when absent or `*`.
+fn if_match_version(headers: &HeaderMap, id: Uuid) -> Result<Option<i32>, ApiError> {
+    let malformed = || ApiError::bad_request("If-Match must be an ETag of the entry or a version number");
+    let value = match headers.get(header::IF_MATCH) {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    let value = value.to_str().map_err(|_| malformed())?.trim();
+    if value == "*" {
+        return Ok(None);
+    }
+    let value = value.trim_matches('"');
+    // an ETag is `<id>-<version>`, and only ever matches the entry it came from
+    let version = match value.rsplit_once('-') {
+        Some((tagged, version)) => {
+            let tagged: Uuid = tagged.parse().map_err(|_| malformed())?;
+            if tagged != id {
+                return Err(ApiError::new(StatusCode::CONFLICT, format!("If-Match is the ETag of blog entry {}, not of {}", tagged, id)));
+            }
+            version
+        }
+        None => value,
+    };
+    version.parse().map(Some).map_err(|_| malformed())
+}
+
+/// Explains why a conditional update touched no rows: the entry is gone, or its version moved on.
+async fn not_updated(tx: &mut Transaction<'_, Postgres>, id: Uuid) -> ApiError {
+    let exists: Result<(bool,), _> = sqlx::query_as("select exists (select 1 from blog_entry where id = $1 and deleted_at is null)")
+        .bind(id)
+        .fetch_one(&mut *tx)
+        .await;
+    match exists {
+        Ok((true,)) => ApiError::new(StatusCode::CONFLICT, format!("blog entry {} was changed by someone else", id)),
+        Ok((false,)) => ApiError::not_found(format!("blog entry {} not found", id)),
+        Err(e) => internal_error(e),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/entries/{id}",
+    params(("id" = Uuid, Path, description = "Id of the entry")),
+    responses(
+        (status = 204, description = "Entry deleted, it is kept but hidden from then on"),
+        (status = 401, description = "Missing or invalid X-Api-Key"),
+        (status = 404, description = "No entry with this id"),
+    ),
+    security(("api_key" = []))
+)]
+async fn delete_blog(Extension(state): Extension<AppState>, Path(id): Path<Uuid>) -> Result<StatusCode, ApiError> {
+    let mut tx = state.pool.begin().await.map_err(internal_error)?;
+    let result = async {
+        let result = sqlx::query("update blog_entry set deleted_at = now(), updated = now(), version = version + 1 where id = $1 and deleted_at is null")
+            .bind(id)
+            .execute(&mut tx)
+            .await
+            .map_err(internal_error)?;
+        if result.rows_affected() == 0 {
+            return Err(ApiError::not_found(format!("blog entry {} not found", id)));
+        }
+        notify_changed(&mut tx, id).await.map_err(internal_error)
+    }.await;
+    finish_transaction(tx, result).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/entries/{id}/restore",
+    params(("id" = Uuid, Path, description = "Id of the entry")),
+    responses(
+        (status = 200, description = "The restored entry", body = BlogEntry),
+        (status = 401, description = "Missing or invalid X-Api-Key"),
+        (status = 404, description = "No entry with this id"),
+        (status = 409, description = "The entry is not deleted"),
+    ),
+    security(("api_key" = []))
+)]
+async fn restore_blog(Extension(state): Extension<AppState>, Path(id): Path<Uuid>) -> Result<Json<BlogEntry>, ApiError> {
+    let mut tx = state.pool.begin().await.map_err(internal_error)?;
+    let result = async {
+        let sql = format!("update blog_entry set deleted_at = null, updated = now(), version = version + 1 where id = $1 and deleted_at is not null returning {}", ENTRY_COLUMNS);
+        let restored: Option<BlogEntry> = sqlx::query_as(&sql)
+            .bind(id)
+            .fetch_optional(&mut tx)
+            .await
+            .map_err(internal_error)?;
+        let entry = match restored {
+            Some(entry) => entry,
+            None => {
+                // nothing to restore, tell a missing entry apart from one that isn't deleted
+                let (exists,): (bool,) = sqlx::query_as("select exists (select 1 from blog_entry where id = $1)")
+                    .bind(id)
+                    .fetch_one(&mut tx)
+                    .await
+                    .map_err(internal_error)?;
+                return Err(if exists {
+                    ApiError::new(StatusCode::CONFLICT, format!("blog entry {} is not deleted", id))
+                } else {
+                    ApiError::not_found(format!("blog entry {} not found", id))
+                });
+            }
+        };
+        notify_changed(&mut tx, id).await.map_err(internal_error)?;
+        Ok(entry.with_reading_time())
+    }.await;
+    finish_transaction(tx, result).await.map(Json)
+}
+
+#[utoipa::path(
+    get,
+    path = "/entries/{id}/comments",
+    params(("id" = Uuid, Path, description = "Id of the entry")),
+    responses(
+        (status = 200, description = "The comments on the entry, oldest first", body = [Comment]),
+        (status = 404, description = "No entry with this id"),
+    )
+)]
+async fn get_comments(Extension(state): Extension<AppState>, Path(id): Path<Uuid>) -> Result<Json<Vec<Comment>>, ApiError> {
+    let (exists,): (bool,) = sqlx::query_as("select exists (select 1 from blog_entry where id = $1 and deleted_at is null)")
+        .bind(id)
+        .fetch_one(&state.read_pool)
+        .await
+        .map_err(internal_error)?;
+    if !exists {
+        return Err(ApiError::not_found(format!("blog entry {} not found", id)));
+    }
+
+    sqlx::query_as("select id, entry_id, author, body, created from comment where entry_id = $1 order by created, id")
+        .bind(id)
+        .fetch_all(&state.read_pool)
+        .await
+        .map(Json)
+        .map_err(internal_error)
+}
+
+#[utoipa::path(
+    post,
+    path = "/entries/{id}/comments",
+    params(("id" = Uuid, Path, description = "Id of the entry")),
+    request_body = NewComment,
+    responses(
+        (status = 201, description = "The new comment", body = Comment),
+        (status = 400, description = "Malformed comment"),
+        (status = 422, description = "Invalid comment"),
+        (status = 401, description = "Missing or invalid X-Api-Key or bearer token, both are required"),
+        (status = 404, description = "No entry with this id"),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+async fn add_comment(Extension(state): Extension<AppState>, Path(id): Path<Uuid>, user: AuthUser, ValidatedJson(comment): ValidatedJson<NewComment>) -> Result<(StatusCode, Json<Comment>), ApiError> {
+    check_author(&user)?;
+
+    // inserts nothing when the entry doesn't exist, rather than failing on the foreign key
+    sqlx::query_as(
+        "insert into comment (entry_id, author, body, created) \
+         select $1, $2, $3, $4 where exists (select 1 from blog_entry where id = $1 and deleted_at is null) \
+         returning id, entry_id, author, body, created",
+    )
+        .bind(id)
+        .bind(&user.sub)
+        .bind(&comment.body)
+        .bind(Utc::now())
+        .fetch_optional(&state.pool)
+        .await
+        .map_err(map_db_error)?
+        .map(|comment| (StatusCode::CREATED, Json(comment)))
+        .ok_or_else(|| ApiError::not_found(format!("blog entry {} not found", id)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/authors/{email}/stats",
+    params(("email" = String, Path, description = "Email address of the author")),
+    responses(
+        (status = 200, description = "How many entries the author published and when", body = AuthorStats),
+        (status = 400, description = "Not an email address"),
+        (status = 404, description = "The author has no entries"),
+    )
+)]
+async fn author_stats(Extension(state): Extension<AppState>, Path(email): Path<String>) -> Result<Json<AuthorStats>, ApiError> {
+    if !validator::validate_email(&email) {
+        return Err(ApiError::bad_request(format!("{:?} is not an email address", email)));
+    }
+    let email = normalize_email(&email);
+
+    sqlx::query_as(
+        "select author, count(*) as entry_count, min(created) as first_post, max(created) as last_post \
+         from blog_entry where author = $1 and deleted_at is null group by author",
+    )
+        .bind(&email)
+        .fetch_optional(&state.read_pool)
+        .await
+        .map_err(internal_error)?
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found(format!("author {} has no entries", email)))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/authors/{email}",
+    params(("email" = String, Path, description = "The current email address of the author")),
+    request_body = AuthorReassignment,
+    responses(
+        (status = 200, description = "How many entries were moved to the new email address", body = Reassigned),
+        (status = 400, description = "Malformed body or not an email address"),
+        (status = 422, description = "Invalid new email address"),
+        (status = 401, description = "Missing or invalid X-Api-Key or bearer token, both are required"),
+        (status = 403, description = "The token is not an admin token"),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+async fn reassign_author(
+    Extension(state): Extension<AppState>,
+    _admin: AdminUser,
+    Path(email): Path<String>,
+    ValidatedJson(reassignment): ValidatedJson<AuthorReassignment>,
+) -> Result<Json<Reassigned>, ApiError> {
+    if !validator::validate_email(&email) {
+        return Err(ApiError::bad_request(format!("{:?} is not an email address", email)));
+    }
+    let email = normalize_email(&email);
+
+    // one transaction, so the author is never left spread over both addresses
+    let mut tx = state.pool.begin().await.map_err(internal_error)?;
+    let result = async {
+        let ids: Vec<(Uuid,)> = sqlx::query_as("update blog_entry set author = $2, updated = now(), version = version + 1 where author = $1 returning id")
+            .bind(&email)
+            .bind(&reassignment.new_email)
+            .fetch_all(&mut tx)
+            .await
+            .map_err(map_db_error)?;
+        for (id,) in &ids {
+            notify_changed(&mut tx, *id).await.map_err(internal_error)?;
+        }
+        Ok(Reassigned { updated: ids.len() as u64 })
+    }.await;
+    finish_transaction(tx, result).await.map(Json)
+}
+
+#[utoipa::path(
+    get,
+    path = "/authors/counts",
+    params(AuthorCountParams),
+    responses(
+        (status = 200, description = "The number of entries of every author, most entries first", body = [AuthorCount]),
+        (status = 400, description = "Malformed limit"),
+        (status = 422, description = "A limit below 1"),
+    )
+)]
+async fn author_counts(Extension(state): Extension<AppState>, ValidatedQuery(params): ValidatedQuery<AuthorCountParams>) -> Result<Json<Vec<AuthorCount>>, ApiError> {
+    // a null limit is no limit at all
+    sqlx::query_as(
+        "select author, count(*) as count from blog_entry where deleted_at is null \
+         group by author order by count(*) desc, author limit $1",
+    )
+        .bind(params.limit)
+        .fetch_all(&state.read_pool)
+        .await
+        .map(Json)
+        .map_err(internal_error)
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/reindex",
+    responses(
+        (status = 202, description = "The search index is being rebuilt in the background", body = ReindexJob, headers(("location" = String, description = "Where to follow the progress"))),
+        (status = 401, description = "Missing or invalid X-Api-Key or bearer token, both are required"),
+        (status = 403, description = "The token is not an admin token"),
+        (status = 409, description = "A reindex is already running"),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+async fn start_reindex(Extension(state): Extension<AppState>, AdminUser(user): AdminUser) -> Result<Response, ApiError> {
+    let job = ReindexJob { id: Uuid::new_v4(), status: JobStatus::Running, started: Utc::now(), finished: None, duration_ms: None, error: None };
+    {
+        let mut jobs = state.reindex_jobs.lock().expect("reindex jobs lock poisoned");
+        if jobs.values().any(|job| job.status == JobStatus::Running) {
+            return Err(ApiError::new(StatusCode::CONFLICT, "a reindex is already running"));
+        }
+        jobs.insert(job.id, job.clone());
+    }
+    info!("{} started reindex {}", user.sub, job.id);
+    tokio::spawn(reindex(state.clone(), job.id));
+
+    let prefix = state.config.api_prefix.as_deref().unwrap_or_default();
+    let location = format!("{}/admin/reindex/{}", prefix, job.id);
+    Ok((StatusCode::ACCEPTED, [(header::LOCATION, location)], Json(job)).into_response())
+}
+
+/// Rebuilds the search index and refreshes the planner statistics of `blog_entry`, recording the outcome in job `id`.
+/// `concurrently` keeps the index usable, so requests are not blocked while it is rebuilt.
+async fn reindex(state: AppState, id: Uuid) {
+    let started = std::time::Instant::now();
+    let result = async {
+        sqlx::query("reindex index concurrently blog_entry_search").execute(&state.pool).await?;
+        sqlx::query("analyze blog_entry").execute(&state.pool).await
+    }.await;
+    let elapsed = started.elapsed();
+
+    match &result {
+        Ok(_) => info!("reindex {} finished in {:?}", id, elapsed),
+        Err(err) => warn!("reindex {} failed after {:?}: {}", id, elapsed, err),
+    }
+    let mut jobs = state.reindex_jobs.lock().expect("reindex jobs lock poisoned");
+    if let Some(job) = jobs.get_mut(&id) {
+        job.status = if result.is_ok() { JobStatus::Succeeded } else { JobStatus::Failed };
+        job.finished = Some(Utc::now());
+        job.duration_ms = Some(elapsed.as_millis() as u64);
+        job.error = result.err().map(|err| redact_passwords(&err.to_string()));
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/reindex/{id}",
+    params(("id" = Uuid, Path, description = "Id of the reindex job")),
+    responses(
+        (status = 200, description = "Progress of the reindex", body = ReindexJob),
+        (status = 403, description = "The token is not an admin token"),
+        (status = 404, description = "No reindex with this id was started on this instance"),
+    ),
+    security(("bearer" = []))
+)]
+async fn get_reindex(Extension(state): Extension<AppState>, _admin: AdminUser, Path(id): Path<Uuid>) -> Result<Json<ReindexJob>, ApiError> {
+    state.reindex_jobs
+        .lock()
+        .expect("reindex jobs lock poisoned")
+        .get(&id)
+        .cloned()
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found(format!("reindex {} not found", id)))
+}
+
+/// Postgres channel on which the id of every created, updated or deleted entry is announced.
+const CHANGES_CHANNEL: &str = "blog_changed";
+
+/// Announces a change to entry `id` on `CHANGES_CHANNEL`. Postgres only delivers it when `tx` commits.
+async fn notify_changed(tx: &mut Transaction<'_, Postgres>, id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query("select pg_notify($1, $2)")
+        .bind(CHANGES_CHANNEL)
+        .bind(id.to_string())
+        .execute(&mut *tx)
+        .await?;
+    Ok(())
+}
+
+/// Listens on `CHANGES_CHANNEL`, so changes made by any instance reach the clients of this one,
+/// and forwards the ids to `changes`. Listens on a connection of its own to `database_url`, so it doesn't
+/// take one away from the handlers. Returns once `shutdown` fires.
+pub async fn listen_for_changes(database_url: String, changes: broadcast::Sender<Uuid>, mut shutdown: watch::Receiver<Option<Instant>>) {
+    let mut listener = match PgListener::connect(&database_url).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            warn!("can't listen for changes, the change feed stays silent: {}", err);
+            return;
+        }
+    };
+    if let Err(err) = listener.listen(CHANGES_CHANNEL).await {
+        warn!("can't listen on {}, the change feed stays silent: {}", CHANGES_CHANNEL, err);
+        return;
+    }
+
+    loop {
+        let received = tokio::select! {
+            received = listener.recv() => received,
+            _ = shutdown.changed() => return,
+        };
+        match received {
+            Ok(notification) => match notification.payload().parse::<Uuid>() {
+                Ok(id) => {
+                    debug!("blog entry {} changed", id);
+                    // nobody listening is fine
+                    let _ = changes.send(id);
+                }
+                Err(_) => warn!("ignoring malformed change notification {:?}", notification.payload()),
+            },
+            Err(err) => {
+                warn!("receiving change notifications failed, retrying: {}", err);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        }
+    }
+}
+
+/// Streams the id of every entry that gets created, updated or deleted as a `changed` event.
+#[utoipa::path(
+    get,
+    path = "/entries/events",
+    responses(
+        (status = 200, description = "Server-sent `changed` events carrying the id of each created, updated or deleted entry", content_type = "text/event-stream"),
+    )
+)]
+async fn entry_events(Extension(state): Extension<AppState>) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let mut receiver = state.changes.subscribe();
+    let events = async_stream::stream! {
+        loop {
+            match receiver.recv().await {
+                Ok(id) => yield Ok(Event::default().event("changed").data(id.to_string())),
+                Err(broadcast::error::RecvError::Lagged(skipped)) => warn!("change feed subscriber lagged, skipped {} changes", skipped),
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    };
+    Sse::new(events).keep_alive(KeepAlive::new().interval(SSE_KEEP_ALIVE))
+}
+
+/// How often idle event streams get a keep-alive comment, so proxies don't drop them.
+const SSE_KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// Streams every entry created on this instance from now on as a `created` event.
+#[utoipa::path(
+    get,
+    path = "/entries/stream",
+    responses(
+        (status = 200, description = "Server-sent `created` events carrying each new entry as JSON", content_type = "text/event-stream"),
+    )
+)]
+async fn entry_stream(Extension(state): Extension<AppState>) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let mut receiver = state.created.subscribe();
+    let events = async_stream::stream! {
+        loop {
+            match receiver.recv().await {
+                Ok(entry) => match Event::default().event("created").json_data(&entry) {
+                    Ok(event) => yield Ok(event),
+                    Err(err) => warn!("can't serialize blog entry {:?} for the stream: {}", entry.id, err),
+                },
+                Err(broadcast::error::RecvError::Lagged(skipped)) => warn!("entry stream subscriber lagged, skipped {} entries", skipped),
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    };
+    Sse::new(events).keep_alive(KeepAlive::new().interval(SSE_KEEP_ALIVE))
+}
+
+/// How long the readiness probe waits for the database before reporting it unavailable.
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[utoipa::path(
+    get,
+    path = "/health",
+    responses(
+        (status = 200, description = "The database is reachable", body = Health),
+        (status = 503, description = "The database is unreachable", body = Health),
+    )
+)]
+async fn health(Extension(state): Extension<AppState>) -> (StatusCode, Json<Health>) {
+    match probe_database(&state.pool).await {
+        Ok(_) => (StatusCode::OK, Json(Health { status: "ok" })),
+        Err(_) => (StatusCode::SERVICE_UNAVAILABLE, Json(Health { status: "unavailable" })),
+    }
+}
+
+/// Runs `select 1` on a connection from `pool` within `HEALTH_CHECK_TIMEOUT`, returning how long that took.
+async fn probe_database(pool: &PgPool) -> Result<Duration, String> {
+    let started = Instant::now();
+    let check = async {
+        let mut connection = pool.acquire().await?;
+        sqlx::query("select 1").execute(&mut connection).await
+    };
+
+    match tokio::time::timeout(HEALTH_CHECK_TIMEOUT, check).await {
+        Ok(Ok(_)) => Ok(started.elapsed()),
+        Ok(Err(err)) => {
+            let err = redact_passwords(&err.to_string());
+            warn!("health check failed: {}", err);
+            Err(err)
+        }
+        Err(_) => {
+            warn!("health check timed out after {:?}", HEALTH_CHECK_TIMEOUT);
+            Err(format!("timed out after {:?}", HEALTH_CHECK_TIMEOUT))
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/health/detailed",
+    responses(
+        (status = 200, description = "The database is reachable and its schema is up to date", body = DetailedHealth),
+        (status = 503, description = "The database is unreachable or migrations are missing, `failing` names which", body = DetailedHealth),
+    )
+)]
+async fn detailed_health(Extension(state): Extension<AppState>) -> (StatusCode, Json<DetailedHealth>) {
+    let pool = PoolHealth { size: state.pool.size(), idle: state.pool.num_idle() };
+    let database = match probe_database(&state.pool).await {
+        Ok(latency) => ComponentHealth { status: "ok", latency_ms: Some(latency.as_secs_f64() * 1000.0), error: None },
+        Err(err) => ComponentHealth { status: "unavailable", latency_ms: None, error: Some(err) },
+    };
+    let migrations = migration_health(&state.pool).await;
+
+    let failing: Vec<_> = [("database", database.status), ("migrations", migrations.status)]
+        .into_iter()
+        .filter(|(_, status)| *status != "ok")
+        .map(|(component, _)| component)
+        .collect();
+    let status = if failing.is_empty() { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    let health = DetailedHealth {
+        status: if failing.is_empty() { "ok" } else { "unavailable" },
+        failing,
+        uptime_secs: state.started.elapsed().as_secs(),
+        pool,
+        database,
+        migrations,
+    };
+    (status, Json(health))
+}
+
+/// Compares the migrations built into this binary with the ones recorded as applied; a failed one counts as pending.
+async fn migration_health(pool: &PgPool) -> MigrationHealth {
+    let applied: Result<Vec<(i64,)>, _> = sqlx::query_as("select version from _sqlx_migrations where success")
+        .fetch_all(pool)
+        .await;
+    match applied {
+        Ok(applied) => {
+            let pending = sqlx::migrate!().iter().filter(|migration| !applied.iter().any(|(version,)| *version == migration.version)).count();
+            MigrationHealth { status: if pending == 0 { "ok" } else { "pending" }, applied: Some(applied.len()), pending: Some(pending), error: None }
+        }
+        Err(err) => MigrationHealth { status: "unavailable", applied: None, pending: None, error: Some(redact_passwords(&err.to_string())) },
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+struct Health {
+    #[schema(example = "ok")]
+    status: &'static str,
+}
+
+#[derive(Serialize, ToSchema)]
+struct DetailedHealth {
+    /// `ok`, or `unavailable` when any component is failing.
+    #[schema(example = "ok")]
+    status: &'static str,
+    /// The components that are not `ok`.
+    #[schema(example = json!([]))]
+    failing: Vec<&'static str>,
+    uptime_secs: u64,
+    pool: PoolHealth,
+    database: ComponentHealth,
+    migrations: MigrationHealth,
+}
+
+/// Connections of the primary pool.
+#[derive(Serialize, ToSchema)]
+struct PoolHealth {
+    size: u32,
+    idle: usize,
+}
+
+/// The `select 1` probe.
+#[derive(Serialize, ToSchema)]
+struct ComponentHealth {
+    #[schema(example = "ok")]
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    latency_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct MigrationHealth {
+    /// `ok`, `pending` when some migrations are not applied yet, or `unavailable` when they can't be read.
+    #[schema(example = "ok")]
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    applied: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pending: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/version",
+    responses(
+        (status = 200, description = "Which build of the service is running", body = Version),
+    )
+)]
+async fn version() -> Json<Version> {
+    let built_at = env!("BUILT_AT").parse().ok().and_then(|secs| Utc.timestamp_opt(secs, 0).single());
+    Json(Version {
+        version: env!("CARGO_PKG_VERSION"),
+        git_sha: env!("GIT_SHA"),
+        built_at: built_at.expect("build.rs sets BUILT_AT to a unix timestamp"),
+    })
+}
+
+/// Build information, captured by `build.rs`.
+#[derive(Serialize, ToSchema)]
+struct Version {
+    #[schema(example = "0.1.0")]
+    version: &'static str,
+    /// The commit the service was built from, `unknown` when built outside of a git checkout.
+    git_sha: &'static str,
+    built_at: DateTime<Utc>,
+}
+
+/// Retries database operations that failed because the connection to the database was lost.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// The same policy, but retrying at most once, for operations that are not idempotent.
+    pub fn at_most_once(self) -> Self {
+        RetryPolicy { max_retries: self.max_retries.min(1), ..self }
+    }
+
+    /// Runs `operation`, retrying transient failures with exponential backoff starting at `base_delay`.
+    pub async fn run<T, F, Fut>(&self, mut operation: F) -> Result<T, sqlx::Error>
+        where
+            F: FnMut() -> Fut,
+            Fut: Future<Output = Result<T, sqlx::Error>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match operation().await {
+                Err(err) if attempt < self.max_retries && is_transient(&err) => {
+                    let delay = self.delay(attempt);
+                    warn!("transient database error, retrying in {:?}: {}", delay, err);
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// How long to wait before retry number `attempt`, counting from 0: doubling every time, up to `MAX_RETRY_DELAY`.
+    fn delay(&self, attempt: u32) -> Duration {
+        2u32.checked_pow(attempt)
+            .and_then(|factor| self.base_delay.checked_mul(factor))
+            .map_or(MAX_RETRY_DELAY, |delay| delay.min(MAX_RETRY_DELAY))
+    }
+}
+
+/// The longest `RetryPolicy` waits between two attempts.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Whether `err` is a connection level failure that may succeed when tried again,
+/// as opposed to an error in the query or a violated constraint.
+fn is_transient(err: &sqlx::Error) -> bool {
+    match err {
+        sqlx::Error::Io(_) | sqlx::Error::Tls(_) => true,
+        // class 08 is connection exception, 57P01..57P03 are the server shutting down or starting up
+        sqlx::Error::Database(db_err) => db_err
+            .code()
+            .map(|code| code.starts_with("08") || matches!(code.as_ref(), "57P01" | "57P02" | "57P03"))
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
+/// Response header carrying the total number of entries, regardless of pagination.
+const X_TOTAL_COUNT: HeaderName = HeaderName::from_static("x-total-count");
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::config::RateLimit;
+    use crate::test_support::{admin_bearer_token, bearer_token, spawn_app, test_app, test_app_with, TestDb};
+
+    async fn post_entry(app: Router, body: &'static str) -> (StatusCode, serde_json::Value) {
+        let request = Request::post("/entries")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::AUTHORIZATION, bearer_token("author@example.com"))
+            .body(Body::from(body))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn compresses_large_list_responses() {
+        let db = TestDb::new().await;
+        for i in 0..100 {
+            sqlx::query("insert into blog_entry (created, title, author, text) values (now(), $1, 'test@example.com', $2)")
+                .bind(format!("Test entry number {}", i))
+                .bind("Lorem ipsum dolor sit amet, consectetur adipiscing elit. ".repeat(20))
+                .execute(&db.pool)
+                .await
+                .unwrap();
+        }
+
+        let request = Request::get("/entries")
+            .header(header::ACCEPT_ENCODING, "gzip")
+            .body(Body::empty())
+            .unwrap();
+        let response = test_app(db.pool.clone()).oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+    }
+
+    #[tokio::test]
+    async fn streams_entries_as_ndjson() {
+        let db = TestDb::new().await;
+        for i in 0..3 {
+            sqlx::query("insert into blog_entry (created, title, author, text) values (now(), $1, 'test@example.com', 'Lorem ipsum')")
+                .bind(format!("Streamed entry {}", i))
+                .execute(&db.pool)
+                .await
+                .unwrap();
+        }
+
+        let request = Request::get("/entries?author=test@example.com")
+            .header(header::ACCEPT, "application/x-ndjson")
+            .body(Body::empty())
+            .unwrap();
+        let response = test_app(db.pool.clone()).oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/x-ndjson");
+        assert!(response.headers().get_all(header::VARY).iter().any(|vary| vary == "accept"));
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let entries: Vec<serde_json::Value> = std::str::from_utf8(&body)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0]["author"], "test@example.com");
+        assert_eq!(entries[0]["reading_time_minutes"], 1);
+
+        // the page is served on the same url, caches must tell the two apart
+        let request = Request::get("/entries?author=test@example.com").body(Body::empty()).unwrap();
+        let response = test_app(db.pool.clone()).oneshot(request).await.unwrap();
+        assert!(response.headers().get_all(header::VARY).iter().any(|vary| vary == "accept"));
+    }
+
+    #[tokio::test]
+    async fn filters_entries_by_title_and_author_ignoring_case() {
+        let db = TestDb::new().await;
+        for (title, author) in [("100% Rust", "Filter@Example.com"), ("1000 Rust tips", "filter@example.com"), ("100% Rust", "other@example.com")] {
+            sqlx::query("insert into blog_entry (created, title, author, text) values (now(), $1, $2, 'Lorem ipsum')")
+                .bind(title)
+                .bind(author)
+                .execute(&db.pool)
+                .await
+                .unwrap();
+        }
+        let list = |uri: &'static str| test_app(db.pool.clone()).oneshot(Request::get(uri).body(Body::empty()).unwrap());
+
+        // the % is matched literally, so "1000 Rust tips" is left out
+        let response = list("/entries?title_contains=0%25%20rUST&author=filter@example.com").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let page: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(page["total"], 1);
+        assert_eq!(page["items"][0]["author"], "Filter@Example.com");
+
+        let response = list("/entries?title_contains=%20").await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn filters_entries_by_creation_date() {
+        let db = TestDb::new().await;
+        for created in ["2022-12-31T23:59:59Z", "2023-06-01T12:00:00Z", "2024-01-01T00:00:00Z"] {
+            sqlx::query("insert into blog_entry (created, title, author, text) values ($1::timestamptz, 'Archived', 'archive@example.com', 'Lorem ipsum')")
+                .bind(created)
+                .execute(&db.pool)
+                .await
+                .unwrap();
+        }
+        let list = |uri: &'static str| test_app(db.pool.clone()).oneshot(Request::get(uri).body(Body::empty()).unwrap());
+
+        let response = list("/entries?author=archive@example.com&from=2023-01-01T00:00:00Z&to=2023-12-31T23:59:59Z").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let page: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(page["total"], 1);
+        assert_eq!(page["items"][0]["created"], "2023-06-01T12:00:00Z");
+
+        let response = list("/entries?from=2024-01-01T00:00:00Z&to=2023-01-01T00:00:00Z").await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn counts_entries_without_sending_them() {
+        let db = TestDb::new().await;
+        for _ in 0..2 {
+            sqlx::query("insert into blog_entry (created, title, author, text) values (now(), 'Counted', 'count@example.com', 'Lorem ipsum')")
+                .execute(&db.pool)
+                .await
+                .unwrap();
+        }
+        let request = Request::head("/entries?author=count@example.com").body(Body::empty()).unwrap();
+        let response = test_app(db.pool.clone()).oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["x-total-count"], "2");
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert!(body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn answers_not_modified_while_the_list_is_unchanged() {
+        let db = TestDb::new().await;
+        sqlx::query("insert into blog_entry (created, updated, title, author, text) values ('2023-06-01T12:00:00.5Z', '2023-06-01T12:00:00.5Z', 'Cached', 'cache@example.com', 'Lorem ipsum')")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        let list = |since: &'static str| {
+            let request = Request::get("/entries?author=cache@example.com").header(header::IF_MODIFIED_SINCE, since);
+            test_app(db.pool.clone()).oneshot(request.body(Body::empty()).unwrap())
+        };
+
+        let response = list("Thu, 01 Jun 2023 11:59:59 GMT").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::LAST_MODIFIED], "Thu, 01 Jun 2023 12:00:00 GMT");
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "max-age=0");
+
+        let response = list("Thu, 01 Jun 2023 12:00:00 GMT").await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[header::LAST_MODIFIED], "Thu, 01 Jun 2023 12:00:00 GMT");
+    }
+
+    #[tokio::test]
+    async fn answers_the_list_again_once_an_entry_is_deleted_or_restored() {
+        let db = TestDb::new().await;
+        let mut ids = Vec::new();
+        for title in ["Cached and kept", "Cached and deleted"] {
+            let (id,): (Uuid,) = sqlx::query_as("insert into blog_entry (created, updated, title, author, text) values ('2023-06-01T12:00:00Z', '2023-06-01T12:00:00Z', $1, 'cache@example.com', 'Lorem ipsum') returning id")
+                .bind(title)
+                .fetch_one(&db.pool)
+                .await
+                .unwrap();
+            ids.push(id);
+        }
+        let list = || {
+            let request = Request::get("/entries?author=cache@example.com").header(header::IF_MODIFIED_SINCE, "Thu, 01 Jun 2023 12:00:00 GMT");
+            test_app(db.pool.clone()).oneshot(request.body(Body::empty()).unwrap())
+        };
+        assert_eq!(list().await.unwrap().status(), StatusCode::NOT_MODIFIED);
+
+        let request = Request::delete(format!("/entries/{}", ids[1])).body(Body::empty()).unwrap();
+        assert_eq!(test_app(db.pool.clone()).oneshot(request).await.unwrap().status(), StatusCode::NO_CONTENT);
+        assert_eq!(list().await.unwrap().status(), StatusCode::OK);
+
+        let request = Request::post(format!("/entries/{}/restore", ids[1])).body(Body::empty()).unwrap();
+        assert_eq!(test_app(db.pool.clone()).oneshot(request).await.unwrap().status(), StatusCode::OK);
+        let (deleted,): (i64,) = sqlx::query_as("select count(*) from blog_entry where deleted_at is not null").fetch_one(&db.pool).await.unwrap();
+        assert_eq!(deleted, 0);
+        assert_eq!(list().await.unwrap().status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn summarizes_the_entries_of_an_author() {
+        let db = TestDb::new().await;
+        for created in ["2023-01-01T00:00:00Z", "2023-06-01T12:00:00Z"] {
+            sqlx::query("insert into blog_entry (created, title, author, text) values ($1::timestamptz, 'Profiled', 'stats@example.com', 'Lorem ipsum')")
+                .bind(created)
+                .execute(&db.pool)
+                .await
+                .unwrap();
+        }
+        let stats = |email: &'static str| test_app(db.pool.clone()).oneshot(Request::get(format!("/authors/{}/stats", email)).body(Body::empty()).unwrap());
+
+        let response = stats("stats@example.com").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["entry_count"], 2);
+        assert_eq!(body["first_post"], "2023-01-01T00:00:00Z");
+        assert_eq!(body["last_post"], "2023-06-01T12:00:00Z");
+
+        assert_eq!(stats("nobody@example.com").await.unwrap().status(), StatusCode::NOT_FOUND);
+        assert_eq!(stats("not-an-email").await.unwrap().status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn pages_through_entries_with_a_cursor() {
+        let db = TestDb::new().await;
+        let insert = |created: &'static str| {
+            sqlx::query("insert into blog_entry (created, title, author, text) values ($1::timestamptz, 'Paged', 'cursor@example.com', 'Lorem ipsum')")
+                .bind(created)
+                .execute(&db.pool)
+        };
+        for created in ["2023-01-01T00:00:00Z", "2023-02-01T00:00:00Z", "2023-03-01T00:00:00Z"] {
+            insert(created).await.unwrap();
+        }
+        let list = |uri: String| async {
+            let response = test_app(db.pool.clone()).oneshot(Request::get(uri).body(Body::empty()).unwrap()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+        };
+
+        let first = list("/entries?author=cursor@example.com&per_page=2".to_owned()).await;
+        assert_eq!(first["items"][1]["created"], "2023-02-01T00:00:00Z");
+        let cursor = first["next_cursor"].as_str().unwrap().to_owned();
+
+        // a new entry would shift an offset by one, the cursor stays put
+        insert("2023-04-01T00:00:00Z").await.unwrap();
+        let second = list(format!("/entries?author=cursor@example.com&per_page=2&after={}", cursor)).await;
+        assert_eq!(second["items"].as_array().unwrap().len(), 1);
+        assert_eq!(second["items"][0]["created"], "2023-01-01T00:00:00Z");
+        assert!(second["next_cursor"].is_null());
+
+        let request = Request::get("/entries?after=garbage").body(Body::empty()).unwrap();
+        let response = test_app(db.pool.clone()).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn replays_posts_with_the_same_idempotency_key() {
+        let db = TestDb::new().await;
+        let post = |body: &'static str| {
+            let request = Request::post("/entries")
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::AUTHORIZATION, bearer_token("idempotent@example.com"))
+                .header("idempotency-key", "retry-me")
+                .body(Body::from(body))
+                .unwrap();
+            test_app(db.pool.clone()).oneshot(request)
+        };
+        let body = r#"{"title": "Posted only once", "text": "Lorem ipsum dolor"}"#;
+
+        let first = post(body).await.unwrap();
+        let location = first.headers()[header::LOCATION].clone();
+        let response = post(body).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()[header::LOCATION], location);
+        let (count,): (i64,) = sqlx::query_as("select count(*) from blog_entry where author = 'idempotent@example.com'")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+
+        let response = post(r#"{"title": "Something else entirely", "text": "Lorem ipsum dolor"}"#).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn does_not_replay_posts_whose_entry_was_deleted() {
+        let db = TestDb::new().await;
+        let post = || {
+            let request = Request::post("/entries")
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::AUTHORIZATION, bearer_token("idempotent@example.com"))
+                .header("idempotency-key", "retry-me")
+                .body(Body::from(r#"{"title": "Posted and deleted", "text": "Lorem ipsum dolor"}"#))
+                .unwrap();
+            test_app(db.pool.clone()).oneshot(request)
+        };
+
+        let response = post().await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let location = response.headers()[header::LOCATION].to_str().unwrap().to_owned();
+        let request = Request::delete(location).body(Body::empty()).unwrap();
+        let response = test_app(db.pool.clone()).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let response = post().await.unwrap();
+        assert_eq!(response.status(), StatusCode::GONE);
+    }
+
+    #[tokio::test]
+    async fn links_to_the_neighbouring_pages() {
+        let db = TestDb::new().await;
+        for _ in 0..5 {
+            sqlx::query("insert into blog_entry (created, title, author, text) values (now(), 'Linked', 'link@example.com', 'Lorem ipsum')")
+                .execute(&db.pool)
+                .await
+                .unwrap();
+        }
+        let links = |uri: &'static str| {
+            let app = test_app(db.pool.clone());
+            async move {
+                let response = app.oneshot(Request::get(uri).body(Body::empty()).unwrap()).await.unwrap();
+                response.headers()[header::LINK].to_str().unwrap().to_owned()
+            }
+        };
+
+        assert_eq!(
+            links("/entries?author=link%40example.com&per_page=2&page=1").await,
+            "</entries?author=link%40example.com&per_page=2&page=0>; rel=\"first\", \
+             </entries?author=link%40example.com&per_page=2&page=0>; rel=\"prev\", \
+             </entries?author=link%40example.com&per_page=2&page=2>; rel=\"next\", \
+             </entries?author=link%40example.com&per_page=2&page=2>; rel=\"last\""
+        );
+        assert_eq!(
+            links("/entries?author=link%40example.com&per_page=5").await,
+            "</entries?author=link%40example.com&per_page=5&page=0>; rel=\"first\", \
+             </entries?author=link%40example.com&per_page=5&page=0>; rel=\"last\""
+        );
+    }
+
+    #[tokio::test]
+    async fn returns_only_the_selected_fields() {
+        let db = TestDb::new().await;
+        sqlx::query("insert into blog_entry (created, title, author, text) values (now(), 'Sparse', 'sparse@example.com', 'A very long text')")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        let list = |uri: &'static str| test_app(db.pool.clone()).oneshot(Request::get(uri).body(Body::empty()).unwrap());
+
+        let response = list("/entries?author=sparse@example.com&fields=id,title").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let page: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let entry = page["items"][0].as_object().unwrap();
+        assert_eq!(entry.keys().collect::<Vec<_>>(), ["id", "title"]);
+        assert_eq!(entry["title"], "Sparse");
+
+        let response = list("/entries?author=sparse@example.com&fields=id,reading_time_minutes").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let page: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let entry = page["items"][0].as_object().unwrap();
+        assert_eq!(entry.keys().collect::<Vec<_>>(), ["id", "reading_time_minutes"]);
+        assert_eq!(entry["reading_time_minutes"], 1);
+
+        let response = list("/entries?fields=id,secret").await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn searches_entries_with_their_reading_time() {
+        let db = TestDb::new().await;
+        sqlx::query("insert into blog_entry (created, title, author, text) values (now(), 'Findable entry', 'search@example.com', 'About zeppelins')")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+
+        let request = Request::get("/entries/search?q=zeppelins").body(Body::empty()).unwrap();
+        let response = test_app(db.pool.clone()).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let entries: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(entries[0]["title"], "Findable entry");
+        assert_eq!(entries[0]["reading_time_minutes"], 1);
+    }
+
+    #[tokio::test]
+    async fn counts_the_entries_of_every_author() {
+        let db = TestDb::new().await;
+        sqlx::query("delete from blog_entry").execute(&db.pool).await.unwrap();
+        for author in ["many@example.com", "many@example.com", "few@example.com"] {
+            sqlx::query("insert into blog_entry (created, title, author, text) values (now(), 'Counted', $1, 'Lorem ipsum')")
+                .bind(author)
+                .execute(&db.pool)
+                .await
+                .unwrap();
+        }
+        let counts = |uri: &'static str| test_app(db.pool.clone()).oneshot(Request::get(uri).body(Body::empty()).unwrap());
+
+        let response = counts("/authors/counts?limit=1").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body, serde_json::json!([{"author": "many@example.com", "count": 2}]));
+
+        assert_eq!(counts("/authors/counts?limit=0").await.unwrap().status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn reassigns_the_entries_of_an_author() {
+        let db = TestDb::new().await;
+        for _ in 0..2 {
+            sqlx::query("insert into blog_entry (created, title, author, text) values (now(), 'Moved', 'old@example.com', 'Lorem ipsum')")
+                .execute(&db.pool)
+                .await
+                .unwrap();
+        }
+        let reassign = |token: String, body: &'static str| {
+            let request = Request::patch("/authors/Old@example.com")
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::AUTHORIZATION, token)
+                .body(Body::from(body))
+                .unwrap();
+            test_app(db.pool.clone()).oneshot(request)
+        };
+
+        let response = reassign(bearer_token("old@example.com"), r#"{"new_email": "new@example.com"}"#).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = reassign(admin_bearer_token("admin@example.com"), r#"{"new_email": "not an email"}"#).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let response = reassign(admin_bearer_token("admin@example.com"), r#"{"new_email": "New@Example.com"}"#).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(&body).unwrap()["updated"], 2);
+        let (moved,): (i64,) = sqlx::query_as("select count(*) from blog_entry where author = 'new@example.com'")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(moved, 2);
+    }
+
+    #[tokio::test]
+    async fn finds_entries_by_their_slug() {
+        let db = TestDb::new().await;
+        let mut slugs = Vec::new();
+        for _ in 0..2 {
+            let (status, entry) = post_entry(test_app(db.pool.clone()), r#"{"title": "Hello, Slugged World!", "text": "Lorem ipsum dolor"}"#).await;
+            assert_eq!(status, StatusCode::CREATED);
+            slugs.push(entry["slug"].as_str().unwrap().to_owned());
+        }
+        assert_eq!(slugs, ["hello-slugged-world", "hello-slugged-world-2"]);
+
+        let request = Request::get("/entries/by-slug/hello-slugged-world-2").body(Body::empty()).unwrap();
+        let response = test_app(db.pool.clone()).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let entry: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(entry["slug"], "hello-slugged-world-2");
+        assert_eq!(entry["reading_time_minutes"], 1);
+
+        let request = Request::get("/entries/by-slug/no-such-entry").body(Body::empty()).unwrap();
+        let response = test_app(db.pool.clone()).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn renders_entries_as_html() {
+        let db = TestDb::new().await;
+        let (status, entry) = post_entry(test_app(db.pool.clone()), r#"{"title": "Rendered from markdown", "text": "Some **bold** text <img src=x onerror=steal()>"}"#).await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let request = Request::get(format!("/entries/{}/html", entry["id"].as_str().unwrap())).body(Body::empty()).unwrap();
+        let response = test_app(db.pool.clone()).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "text/html; charset=utf-8");
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(std::str::from_utf8(&body).unwrap(), "<p>Some <strong>bold</strong> text </p>");
+    }
+
+    #[tokio::test]
+    async fn returns_the_latest_entry() {
+        let db = TestDb::new().await;
+        sqlx::query("delete from blog_entry").execute(&db.pool).await.unwrap();
+        let latest = || test_app(db.pool.clone()).oneshot(Request::get("/entries/latest").body(Body::empty()).unwrap());
+        assert_eq!(latest().await.unwrap().status(), StatusCode::NOT_FOUND);
+
+        sqlx::query(
+            "insert into blog_entry (created, title, author, text) values \
+             ('2024-01-01T00:00:00Z', 'The older entry', 'a@example.com', 'Lorem ipsum'), \
+             ('2024-03-01T00:00:00Z', 'The newest entry', 'a@example.com', 'Lorem ipsum'), \
+             ('2024-02-01T00:00:00Z', 'The middle entry', 'a@example.com', 'Lorem ipsum')",
+        )
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        let response = latest().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let entry: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(entry["title"], "The newest entry");
+    }
+
+    #[tokio::test]
+    async fn returns_a_random_entry() {
+        let db = TestDb::new().await;
+        sqlx::query("delete from blog_entry").execute(&db.pool).await.unwrap();
+        let random = || test_app(db.pool.clone()).oneshot(Request::get("/entries/random").body(Body::empty()).unwrap());
+        assert_eq!(random().await.unwrap().status(), StatusCode::NOT_FOUND);
+
+        sqlx::query("insert into blog_entry (created, title, author, text) values (now(), 'The only entry', 'a@example.com', 'Lorem ipsum')")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        let response = random().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let entry: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(entry["title"], "The only entry");
+    }
+
+    #[tokio::test]
+    async fn validates_the_pagination() {
+        let db = TestDb::new().await;
+        let list = |uri: &'static str| test_app(db.pool.clone()).oneshot(Request::get(uri).body(Body::empty()).unwrap());
+
+        let response = list("/entries?per_page=0").await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["details"]["per_page"][0], "per_page must be at least 1");
+
+        assert_eq!(list("/entries?per_page=many").await.unwrap().status(), StatusCode::BAD_REQUEST);
+        assert_eq!(list("/entries?per_page=1").await.unwrap().status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn lets_browsers_send_conditional_requests_and_read_the_response_headers() {
+        let db = TestDb::new().await;
+        let request = Request::options("/entries")
+            .header(header::ORIGIN, "https://example.com")
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "GET")
+            .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "if-none-match, if-modified-since")
+            .body(Body::empty())
+            .unwrap();
+        let response = test_app(db.pool.clone()).oneshot(request).await.unwrap();
+
+        let allowed = response.headers()[header::ACCESS_CONTROL_ALLOW_HEADERS].to_str().unwrap();
+        for name in ["if-match", "if-none-match", "if-modified-since"] {
+            assert!(allowed.contains(name), "{} not in {}", name, allowed);
+        }
+
+        let request = Request::get("/entries").header(header::ORIGIN, "https://example.com").body(Body::empty()).unwrap();
+        let response = test_app(db.pool.clone()).oneshot(request).await.unwrap();
+
+        let exposed = response.headers()[header::ACCESS_CONTROL_EXPOSE_HEADERS].to_str().unwrap();
+        for name in ["x-total-count", "link", "etag", "last-modified", "location", "x-request-id"] {
+            assert!(exposed.contains(name), "{} not in {}", name, exposed);
+        }
+    }
+
+    #[test]
+    fn caps_the_delay_between_retries() {
+        let policy = RetryPolicy { max_retries: 3, base_delay: Duration::from_millis(50) };
+        assert_eq!(policy.delay(0), Duration::from_millis(50));
+        assert_eq!(policy.delay(2), Duration::from_millis(200));
+        assert_eq!(policy.delay(20), MAX_RETRY_DELAY);
+        assert_eq!(policy.delay(40), MAX_RETRY_DELAY);
+
+        let policy = RetryPolicy { max_retries: 3, base_delay: Duration::from_secs(u64::MAX) };
+        assert_eq!(policy.delay(1), MAX_RETRY_DELAY);
+    }
+
+    #[tokio::test]
+    async fn accepts_authors_with_long_email_addresses() {
+        let db = TestDb::new().await;
+        let author = format!("{}@example.com", "a".repeat(64));
+        let request = Request::post("/entries")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::AUTHORIZATION, bearer_token(&author))
+            .body(Body::from(r#"{"title": "Written by a long address", "text": "long enough text"}"#))
+            .unwrap();
+        let response = test_app(db.pool.clone()).oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["author"], author);
+    }
+
+    #[tokio::test]
+    async fn answers_requests_that_take_too_long_with_a_json_408() {
+        let db = TestDb::new().await;
+        // the list waits for the lock until the request times out
+        let mut lock = db.pool.begin().await.unwrap();
+        sqlx::query("lock table blog_entry in access exclusive mode").execute(&mut lock).await.unwrap();
+
+        let app = test_app_with(db.pool.clone(), |config| config.request_timeout = Duration::from_millis(200));
+        let response = app.oneshot(Request::get("/entries").body(Body::empty()).unwrap()).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT);
+        let request_id = response.headers()["x-request-id"].to_str().unwrap().to_owned();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["status"], 408);
+        assert_eq!(body["request_id"], request_id);
+        lock.rollback().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn exports_entries_as_csv() {
+        let db = TestDb::new().await;
+        sqlx::query("delete from blog_entry").execute(&db.pool).await.unwrap();
+        sqlx::query("insert into blog_entry (created, title, author, text) values ('2024-01-02T03:04:05Z', 'Quoting, tested', 'test@example.com', $1)")
+            .bind("He said \"hi\",\nthen left")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+
+        let request = Request::get("/entries.csv").body(Body::empty()).unwrap();
+        let response = test_app(db.pool.clone()).oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "text/csv");
+        assert_eq!(response.headers()[header::CONTENT_DISPOSITION], "attachment; filename=\"entries.csv\"");
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(
+            std::str::from_utf8(&body).unwrap(),
+            "created,title,author,text\n2024-01-02T03:04:05+00:00,\"Quoting, tested\",test@example.com,\"He said \"\"hi\"\",\nthen left\"\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_entry_with_too_short_title() {
+        let db = TestDb::new().await;
+        let (status, body) = post_entry(test_app(db.pool.clone()), r#"{"title": "short", "text": "long enough text"}"#).await;
+
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["error"], "input validation error");
+        assert_eq!(body["details"]["title"][0], "Title length must be between 10 and 100");
+    }
+
+    #[tokio::test]
+    async fn validates_entries_without_storing_them() {
+        let db = TestDb::new().await;
+        let validate = |body: &'static str| {
+            let request = Request::post("/entries/validate")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body))
+                .unwrap();
+            test_app(db.pool.clone()).oneshot(request)
+        };
+
+        let response = validate(r#"{"title": "A long enough title", "text": "long enough text"}"#).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = validate(r#"{"title": "short", "text": "long enough text"}"#).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["details"]["title"][0], "Title length must be between 10 and 100");
+
+        let (count,): (i64,) = sqlx::query_as("select count(*) from blog_entry where title = 'A long enough title'")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+
+    #[tokio::test]
+    async fn rejects_title_that_is_only_long_because_of_whitespace() {
+        let db = TestDb::new().await;
+        let (status, body) = post_entry(test_app(db.pool.clone()), r#"{"title": "   short     ", "text": "long enough text"}"#).await;
+
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["details"]["title"][0], "Title length must be between 10 and 100");
+    }
+
+    #[tokio::test]
+    async fn filters_entries_by_tag() {
+        let db = TestDb::new().await;
+        let (status, body) = post_entry(
+            test_app(db.pool.clone()),
+            r#"{"title": "A tagged entry title", "text": "long enough text", "tags": ["Rust", "axum"]}"#,
+        ).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let id: Uuid = serde_json::from_value(body["id"].clone()).unwrap();
+        post_entry(test_app(db.pool.clone()), r#"{"title": "An untagged entry title", "text": "long enough text"}"#).await;
+
+        let request = Request::get("/entries?tag=rust").body(Body::empty()).unwrap();
+        let response = test_app(db.pool.clone()).oneshot(request).await.unwrap();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let page: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(page["total"], 1);
+        assert_eq!(page["items"][0]["id"], id.to_string());
+        assert_eq!(page["items"][0]["tags"], serde_json::json!(["axum", "rust"]));
+    }
+
+    #[tokio::test]
+    async fn rejects_malformed_json() {
+        let db = TestDb::new().await;
+        let (status, body) = post_entry(test_app(db.pool.clone()), r#"{"title": "#).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "the request body is not valid JSON: EOF while parsing a value at line 1 column 10");
+    }
+
+    #[tokio::test]
+    async fn explains_why_a_body_is_not_usable_json() {
+        let db = TestDb::new().await;
+        let post = |content_type: &'static str, body: &'static str| {
+            let request = Request::post("/entries/validate")
+                .header(header::CONTENT_TYPE, content_type)
+                .body(Body::from(body))
+                .unwrap();
+            test_app(db.pool.clone()).oneshot(request)
+        };
+        let error = |response: Response| async {
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()["error"].as_str().unwrap().to_owned()
+        };
+
+        let response = post("text/plain", r#"{"title": "A long enough title", "text": "long enough text"}"#).await.unwrap();
+        assert_eq!(error(response).await, "expected a JSON body with Content-Type: application/json");
+        let response = post("application/json", "").await.unwrap();
+        assert_eq!(error(response).await, "the request body is empty, expected a JSON object");
+        let response = post("application/json", r#"{"title": "A long enough title"}"#).await.unwrap();
+        assert_eq!(error(response).await, "the JSON body does not match the expected fields: missing field `text` at line 1 column 32");
+    }
+
+    #[tokio::test]
+    async fn rejects_too_large_bodies() {
+        let db = TestDb::new().await;
+        let text = "x".repeat(2 * 1024 * 1024);
+        let body = format!(r#"{{"title": "A long enough title", "text": "{}"}}"#, text);
+
+        let request = Request::post("/entries")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::CONTENT_LENGTH, body.len())
+            .header(header::AUTHORIZATION, bearer_token("author@example.com"))
+            .body(Body::from(body.clone()))
+            .unwrap();
+        let response = test_app(db.pool.clone()).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        // without a content length the limit is only hit while the json is read
+        let chunks: Vec<Result<String, std::io::Error>> = vec![Ok(body)];
+        let request = Request::post("/entries")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::AUTHORIZATION, bearer_token("author@example.com"))
+            .body(Body::wrap_stream(futures::stream::iter(chunks)))
+            .unwrap();
+        let response = test_app(db.pool.clone()).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"], "request body is too large");
+    }
+
+    #[tokio::test]
+    async fn inserts_valid_entry() {
+        let db = TestDb::new().await;
+        let (status, body) = post_entry(test_app(db.pool.clone()), r#"{"title": "A long enough title", "text": "long enough text"}"#).await;
+
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body["title"], "A long enough title");
+        let (title, author): (String, String) = sqlx::query_as("select title, author from blog_entry where id = $1")
+            .bind(serde_json::from_value::<Uuid>(body["id"].clone()).unwrap())
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(title, "A long enough title");
+        assert_eq!(author, "author@example.com");
+    }
+
+    async fn post_bulk(app: Router, body: &'static str) -> (StatusCode, serde_json::Value) {
+        let request = Request::post("/entries/bulk")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::AUTHORIZATION, bearer_token("author@example.com"))
+            .body(Body::from(body))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn inserts_entries_in_bulk() {
+        let db = TestDb::new().await;
+        let (status, body) = post_bulk(
+            test_app(db.pool.clone()),
+            r#"[{"title": "First bulk entry", "text": "long enough text"}, {"title": "Second bulk entry", "text": "long enough text"}]"#,
+        ).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["inserted"], 2);
+        let (count,): (i64,) = sqlx::query_as("select count(*) from blog_entry where title like '% bulk entry'")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 2);
+    }
+
+    #[tokio::test]
+    async fn gives_entries_inserted_in_bulk_a_slug() {
+        let db = TestDb::new().await;
+        let (status, _) = post_bulk(
+            test_app(db.pool.clone()),
+            r#"[{"title": "The same bulk title", "text": "long enough text"}, {"title": "The same bulk title", "text": "long enough text"}]"#,
+        ).await;
+        assert_eq!(status, StatusCode::OK);
+
+        for slug in ["the-same-bulk-title", "the-same-bulk-title-2"] {
+            let request = Request::get(format!("/entries/by-slug/{}", slug)).body(Body::empty()).unwrap();
+            let response = test_app(db.pool.clone()).oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_bulk_with_an_invalid_entry() {
+        let db = TestDb::new().await;
+        let (status, body) = post_bulk(
+            test_app(db.pool.clone()),
+            r#"[{"title": "First bulk entry", "text": "long enough text"}, {"title": "short", "text": "long enough text"}]"#,
+        ).await;
+
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["details"]["entries[1].title"][0], "Title length must be between 10 and 100");
+        let (count,): (i64,) = sqlx::query_as("select count(*) from blog_entry where title like '% bulk entry'")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+
+    #[tokio::test]
+    async fn rolls_back_bulk_insert_failing_in_a_later_chunk() {
+        let db = TestDb::new().await;
+        let mut entries: Vec<NewBlogEntry> = (0..BULK_CHUNK_SIZE + 10)
+            .map(|i| NewBlogEntry { title: format!("Rolled back entry {}", i), text: "long enough text".to_owned(), tags: Vec::new() })
+            .collect();
+        // too long for the column, so the second statement fails after the first succeeded
+        entries[BULK_CHUNK_SIZE + 5].title = "x".repeat(101);
+
+        let mut tx = db.pool.begin().await.unwrap();
+        let result = insert_entries(&mut tx, &entries, "author@example.com").await;
+        let err = finish_transaction(tx, result).await.unwrap_err();
+
+        assert_eq!(err.status, StatusCode::INTERNAL_SERVER_ERROR);
+        let (count,): (i64,) = sqlx::query_as("select count(*) from blog_entry where title like 'Rolled back entry %'")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+
+    #[tokio::test]
+    async fn announces_deleted_entries_on_the_change_feed() {
+        let db = TestDb::new().await;
+        let (id,): (Uuid,) = sqlx::query_as("insert into blog_entry (created, title, author, text) values (now(), 'Soon to be deleted', 'test@example.com', 'Lorem ipsum') returning id")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        let (changes, mut receiver) = broadcast::channel(16);
+        let (_stop, shutdown) = watch::channel(None);
+        tokio::spawn(listen_for_changes(db.url().to_owned(), changes, shutdown));
+        // give the listener time to subscribe before anything is announced
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let request = Request::delete(format!("/entries/{}", id)).body(Body::empty()).unwrap();
+        let response = test_app(db.pool.clone()).oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let changed = tokio::time::timeout(Duration::from_secs(5), receiver.recv()).await.unwrap().unwrap();
+        assert_eq!(changed, id);
+    }
+
+    #[tokio::test]
+    async fn announces_entries_inserted_in_bulk_on_the_change_feed() {
+        let db = TestDb::new().await;
+        let (changes, mut receiver) = broadcast::channel(16);
+        let (stop, shutdown) = watch::channel(None);
+        let listener = tokio::spawn(listen_for_changes(db.url().to_owned(), changes, shutdown));
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        // the listener has a connection of its own
+        assert_eq!(db.pool.size(), db.pool.num_idle() as u32);
+
+        let (status, _) = post_bulk(
+            test_app(db.pool.clone()),
+            r#"[{"title": "First bulk entry", "text": "long enough text"}, {"title": "Second bulk entry", "text": "long enough text"}]"#,
+        ).await;
+
+        assert_eq!(status, StatusCode::OK);
+        let mut changed = Vec::new();
+        for _ in 0..2 {
+            changed.push(tokio::time::timeout(Duration::from_secs(5), receiver.recv()).await.unwrap().unwrap());
+        }
+        changed.sort();
+        let mut ids: Vec<Uuid> = sqlx::query_scalar("select id from blog_entry where title like '% bulk entry'")
+            .fetch_all(&db.pool)
+            .await
+            .unwrap();
+        ids.sort();
+        assert_eq!(changed, ids);
+
+        stop.send(Some(Instant::now())).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), listener).await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn streams_created_entries_as_events() {
+        use hyper::body::HttpBody;
+
+        let app = spawn_app().await;
+        let request = Request::get(format!("http://{}/entries/stream", app.address))
+            .header(header::ACCEPT_ENCODING, "gzip")
+            .body(hyper::Body::empty())
+            .unwrap();
+        let client = hyper::Client::new();
+        let response = client.request(request).await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "text/event-stream");
+        assert!(response.headers().get(header::CONTENT_ENCODING).is_none());
+
+        let request = Request::post(format!("http://{}/entries", app.address))
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::AUTHORIZATION, bearer_token("author@example.com"))
+            .body(hyper::Body::from(r#"{"title": "A streamed entry", "text": "long enough text"}"#))
+            .unwrap();
+        assert_eq!(client.request(request).await.unwrap().status(), StatusCode::CREATED);
+
+        let mut body = response.into_body();
+        let chunk = tokio::time::timeout(Duration::from_secs(5), body.data()).await.unwrap().unwrap().unwrap();
+        let event = std::str::from_utf8(&chunk).unwrap();
+        assert!(event.starts_with("event:created\ndata:"), "unexpected event {:?}", event);
+        assert!(event.contains(r#""title":"A streamed entry""#));
+        assert!(event.contains(r#""reading_time_minutes":1"#), "unexpected event {:?}", event);
+    }
+
+    #[tokio::test]
+    async fn answers_not_modified_while_the_etag_matches() {
+        let db = TestDb::new().await;
+        let (id,): (Uuid,) = sqlx::query_as("insert into blog_entry (created, title, author, text) values (now(), 'Cached entry title', 'test@example.com', 'Lorem ipsum') returning id")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        let get = |etag: Option<&str>| {
+            let mut request = Request::get(format!("/entries/{}", id));
+            if let Some(etag) = etag {
+                request = request.header(header::IF_NONE_MATCH, etag);
+            }
+            test_app(db.pool.clone()).oneshot(request.body(Body::empty()).unwrap())
+        };
+
+        let response = get(None).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let etag = response.headers()[header::ETAG].to_str().unwrap().to_owned();
+
+        let response = get(Some(&etag)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert!(hyper::body::to_bytes(response.into_body()).await.unwrap().is_empty());
+
+        sqlx::query("update blog_entry set updated = now() + interval '1 second', version = version + 1 where id = $1")
+            .bind(id)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        let response = get(Some(&etag)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_ne!(response.headers()[header::ETAG], etag.as_str());
+    }
+
+    #[tokio::test]
+    async fn rejects_update_created_in_the_future() {
+        let db = TestDb::new().await;
+        let created = (Utc::now() + chrono::Duration::days(1)).to_rfc3339();
+        let body = format!(
+            r#"{{"created": "{}", "title": "A long enough title", "author": "test@example.com", "text": "long enough text"}}"#,
+            created
+        );
+        let request = Request::put(format!("/entries/{}", Uuid::new_v4()))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body))
+            .unwrap();
+        let response = test_app(db.pool.clone()).oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["details"]["created"][0], "created must not be in the future");
+    }
+
+    #[tokio::test]
+    async fn rejects_updates_based_on_a_stale_version() {
+        let db = TestDb::new().await;
+        let (id,): (Uuid,) = sqlx::query_as("insert into blog_entry (created, title, author, text) values (now(), 'Versioned entry', 'test@example.com', 'Lorem ipsum') returning id")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        let patch = |if_match: &str| {
+            let request = Request::patch(format!("/entries/{}", id))
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::IF_MATCH, if_match)
+                .body(Body::from(r#"{"text": "changed text"}"#))
+                .unwrap();
+            test_app(db.pool.clone()).oneshot(request)
+        };
+
+        let response = patch("\"1\"").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["version"], 2);
+
+        let response = patch("\"1\"").await.unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let body = r#"{"created": "2022-01-01T00:00:00Z", "title": "A long enough title", "author": "test@example.com", "text": "long enough text", "version": 1}"#;
+        let request = Request::put(format!("/entries/{}", id))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body))
+            .unwrap();
+        let response = test_app(db.pool.clone()).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let response = patch("2").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn updates_entries_conditionally_on_the_etag_they_were_read_with() {
+        let db = TestDb::new().await;
+        let (id,): (Uuid,) = sqlx::query_as("insert into blog_entry (created, title, author, text) values (now(), 'Versioned entry', 'test@example.com', 'Lorem ipsum') returning id")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        let request = Request::get(format!("/entries/{}", id)).body(Body::empty()).unwrap();
+        let response = test_app(db.pool.clone()).oneshot(request).await.unwrap();
+        let etag = response.headers()[header::ETAG].to_str().unwrap().to_owned();
+        let put = |if_match: String| {
+            let body = r#"{"created": "2022-01-01T00:00:00Z", "title": "A long enough title", "author": "test@example.com", "text": "long enough text"}"#;
+            let request = Request::put(format!("/entries/{}", id))
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::IF_MATCH, if_match)
+                .body(Body::from(body))
+                .unwrap();
+            test_app(db.pool.clone()).oneshot(request)
+        };
+
+        let response = put(etag.clone()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = put(etag).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let response = put(format!("\"{}-2\"", Uuid::new_v4())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn answers_unknown_paths_with_a_json_404() {
+        let db = TestDb::new().await;
+        let request = Request::get("/no/such/path?q=1").body(Body::empty()).unwrap();
+        let response = test_app(db.pool.clone()).oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"], "not found");
+        assert_eq!(body["path"], "/no/such/path");
+    }
+
+    #[tokio::test]
+    async fn lists_the_methods_each_route_serves_in_allow() {
+        let db = TestDb::new().await;
+        let id = Uuid::new_v4();
+        let paths = [
+            "/entries".to_owned(),
+            "/entries.csv".to_owned(),
+            "/entries/bulk".to_owned(),
+            "/entries/validate".to_owned(),
+            "/entries/events".to_owned(),
+            "/entries/stream".to_owned(),
+            "/entries/search".to_owned(),
+            "/entries/latest".to_owned(),
+            "/entries/random".to_owned(),
+            "/entries/by-slug/some-slug".to_owned(),
+            format!("/entries/{}", id),
+            format!("/entries/{}/html", id),
+            format!("/entries/{}/restore", id),
+            format!("/entries/{}/comments", id),
+            "/authors/counts".to_owned(),
+            "/authors/someone%40example.com".to_owned(),
+            "/authors/someone%40example.com/stats".to_owned(),
+            "/admin/reindex".to_owned(),
+            format!("/admin/reindex/{}", id),
+            "/health".to_owned(),
+            "/health/detailed".to_owned(),
+            "/version".to_owned(),
+            "/metrics".to_owned(),
+        ];
+        let methods = [Method::GET, Method::HEAD, Method::POST, Method::PUT, Method::PATCH, Method::DELETE];
+
+        for path in &paths {
+            let mut served = Vec::new();
+            let mut allowed = Vec::new();
+            for method in &methods {
+                let request = Request::builder().method(method).uri(path.as_str()).body(Body::empty()).unwrap();
+                let response = test_app(db.pool.clone()).oneshot(request).await.unwrap();
+                if response.status() == StatusCode::METHOD_NOT_ALLOWED {
+                    allowed.push(response.headers()[header::ALLOW].to_str().unwrap().to_owned());
+                } else {
+                    served.push(method.as_str());
+                }
+            }
+
+            assert!(!allowed.is_empty(), "{} serves every method", path);
+            for allow in allowed {
+                let mut allow: Vec<&str> = allow.split(',').collect();
+                allow.sort_unstable();
+                served.sort_unstable();
+                assert_eq!(allow, served, "Allow of {} doesn't match the methods it serves", path);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn answers_unsupported_methods_with_a_json_405() {
+        let db = TestDb::new().await;
+        let request = Request::post("/entries/search").body(Body::empty()).unwrap();
+        let response = test_app(db.pool.clone()).oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers()[header::ALLOW], "GET,HEAD");
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"], "method not allowed");
+    }
+
+    #[tokio::test]
+    async fn rejects_ids_that_are_not_uuids() {
+        let db = TestDb::new().await;
+        let request = Request::get("/entries/1").body(Body::empty()).unwrap();
+        let response = test_app(db.pool.clone()).oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn comments_on_entries() {
+        let db = TestDb::new().await;
+        let (id,): (Uuid,) = sqlx::query_as("insert into blog_entry (created, title, author, text) values (now(), 'Commented entry', 'test@example.com', 'Lorem ipsum') returning id")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        let post_comment = |id: Uuid| {
+            let request = Request::post(format!("/entries/{}/comments", id))
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::AUTHORIZATION, bearer_token("reader@example.com"))
+                .body(Body::from(r#"{"body": "  Nice post!  "}"#))
+                .unwrap();
+            test_app(db.pool.clone()).oneshot(request)
+        };
+
+        assert_eq!(post_comment(id).await.unwrap().status(), StatusCode::CREATED);
+        assert_eq!(post_comment(Uuid::new_v4()).await.unwrap().status(), StatusCode::NOT_FOUND);
+
+        let request = Request::get(format!("/entries/{}/comments", id)).body(Body::empty()).unwrap();
+        let response = test_app(db.pool.clone()).oneshot(request).await.unwrap();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let comments: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(comments[0]["body"], "Nice post!");
+        assert_eq!(comments[0]["author"], "reader@example.com");
+
+        sqlx::query("delete from blog_entry where id = $1").bind(id).execute(&db.pool).await.unwrap();
+        let (count,): (i64,) = sqlx::query_as("select count(*) from comment").fetch_one(&db.pool).await.unwrap();
+        assert_eq!(count, 0);
+    }
+
+    #[tokio::test]
+    async fn hides_deleted_entries_from_everyone_but_admins() {
+        let db = TestDb::new().await;
+        let (id,): (Uuid,) = sqlx::query_as("insert into blog_entry (created, title, author, text) values (now(), 'Soon to be hidden', 'test@example.com', 'Lorem ipsum') returning id")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        let send = |request: axum::http::request::Builder| test_app(db.pool.clone()).oneshot(request.body(Body::empty()).unwrap());
+
+        assert_eq!(send(Request::delete(format!("/entries/{}", id))).await.unwrap().status(), StatusCode::NO_CONTENT);
+        assert_eq!(send(Request::get(format!("/entries/{}", id))).await.unwrap().status(), StatusCode::NOT_FOUND);
+        assert_eq!(send(Request::delete(format!("/entries/{}", id))).await.unwrap().status(), StatusCode::NOT_FOUND);
+        let (kept,): (bool,) = sqlx::query_as("select exists (select 1 from blog_entry where id = $1)")
+            .bind(id)
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert!(kept);
+
+        let listing = "/entries?author=test@example.com&include_deleted=true";
+        let response = send(Request::get(listing).header(header::AUTHORIZATION, bearer_token("test@example.com"))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = send(Request::get(listing).header(header::AUTHORIZATION, admin_bearer_token("admin@example.com"))).await.unwrap();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let page: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(page["items"][0]["id"], id.to_string());
+        assert!(page["items"][0]["deleted_at"].is_string());
+    }
+
+    #[tokio::test]
+    async fn restores_deleted_entries() {
+        let db = TestDb::new().await;
+        let (id,): (Uuid,) = sqlx::query_as("insert into blog_entry (created, title, author, text, deleted_at) values (now(), 'Deleted for now', 'test@example.com', 'Lorem ipsum', now()) returning id")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        let restore = |id: Uuid| test_app(db.pool.clone()).oneshot(Request::post(format!("/entries/{}/restore", id)).body(Body::empty()).unwrap());
+
+        let response = restore(id).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let entry: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(entry["title"], "Deleted for now");
+        assert!(entry.get("deleted_at").is_none());
+
+        assert_eq!(restore(id).await.unwrap().status(), StatusCode::CONFLICT);
+        assert_eq!(restore(Uuid::new_v4()).await.unwrap().status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn reindexes_in_the_background_for_admins_only() {
+        let db = TestDb::new().await;
+        let app = test_app(db.pool.clone());
+        let send = |request: axum::http::request::Builder, token: String| {
+            app.clone().oneshot(request.header(header::AUTHORIZATION, token).body(Body::empty()).unwrap())
+        };
+
+        let response = send(Request::post("/admin/reindex"), bearer_token("author@example.com")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = send(Request::post("/admin/reindex"), admin_bearer_token("admin@example.com")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let location = response.headers()[header::LOCATION].to_str().unwrap().to_owned();
+
+        let mut status = serde_json::Value::Null;
+        for _ in 0..50 {
+            let response = send(Request::get(&location), admin_bearer_token("admin@example.com")).await.unwrap();
+            let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+            status = serde_json::from_slice::<serde_json::Value>(&body).unwrap()["status"].clone();
+            if status != "running" {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(status, "succeeded");
+    }
+
+    #[tokio::test]
+    async fn sets_security_headers() {
+        let db = TestDb::new().await;
+        let request = Request::get("/entries").body(Body::empty()).unwrap();
+        let response = test_app(db.pool.clone()).oneshot(request).await.unwrap();
+
+        let headers = response.headers();
+        assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+        assert_eq!(headers[header::X_FRAME_OPTIONS], "DENY");
+        assert_eq!(headers[header::REFERRER_POLICY], "no-referrer");
+        assert_eq!(headers[header::CONTENT_SECURITY_POLICY], "default-src 'none'; frame-ancestors 'none'");
+
+        // swagger ui could not run its scripts under that policy
+        let request = Request::get("/swagger-ui/index.html").body(Body::empty()).unwrap();
+        let response = test_app(db.pool.clone()).oneshot(request).await.unwrap();
+        assert_eq!(response.headers()[header::X_FRAME_OPTIONS], "DENY");
+        assert!(response.headers().get(header::CONTENT_SECURITY_POLICY).is_none());
+    }
+
+    #[tokio::test]
+    async fn reports_detailed_health() {
+        let db = TestDb::new().await;
+        let request = Request::get("/health/detailed").body(Body::empty()).unwrap();
+        let response = test_app(db.pool.clone()).oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let health: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(health["status"], "ok");
+        assert_eq!(health["failing"], serde_json::json!([]));
+        assert_eq!(health["database"]["status"], "ok");
+        assert!(health["database"]["latency_ms"].is_number());
+        assert_eq!(health["migrations"]["pending"], 0);
+        assert!(health["pool"]["size"].as_u64().unwrap() >= 1);
+    }
+
+    #[tokio::test]
+    async fn rate_limits_clients_that_make_up_api_keys() {
+        let db = TestDb::new().await;
+        let app = test_app_with(db.pool.clone(), |config| {
+            config.api_key = Some("the-api-key".to_owned());
+            config.rate_limit = Some(RateLimit { rps: 0.1, burst: 2.0 });
+        });
+        let list = |api_key: String| {
+            let request = Request::get("/entries").header(X_API_KEY, api_key).body(Body::empty()).unwrap();
+            app.clone().oneshot(request)
+        };
+
+        for _ in 0..2 {
+            assert_eq!(list(Uuid::new_v4().to_string()).await.unwrap().status(), StatusCode::OK);
+        }
+        assert_eq!(list(Uuid::new_v4().to_string()).await.unwrap().status(), StatusCode::TOO_MANY_REQUESTS);
+        // the real key has a bucket of its own
+        assert_eq!(list("the-api-key".to_owned()).await.unwrap().status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn serves_openapi_document() {
+        let db = TestDb::new().await;
+        let request = Request::get("/api-docs/openapi.json").body(Body::empty()).unwrap();
+        let response = test_app(db.pool.clone()).oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let doc: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert!(doc["paths"]["/entries"]["post"].is_object());
+        assert_eq!(doc["components"]["schemas"]["BlogEntry"]["properties"]["author"]["format"], "email");
+    }
+
+    #[tokio::test]
+    async fn reports_the_build() {
+        let db = TestDb::new().await;
+        let response = test_app(db.pool.clone()).oneshot(Request::get("/version").body(Body::empty()).unwrap()).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let version: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(version["version"], env!("CARGO_PKG_VERSION"));
+        assert!(version["git_sha"].is_string());
+        assert!(version["built_at"].is_string());
+    }
+
+    #[tokio::test]
+    async fn serves_entries_over_http() {
+        let app = spawn_app().await;
+        let (id,): (Uuid,) = sqlx::query_as("insert into blog_entry (created, title, author, text) values (now(), 'Served over http', 'test@example.com', 'Lorem ipsum') returning id")
+            .fetch_one(&app.db.pool)
+            .await
+            .unwrap();
+
+        let uri = format!("http://{}/entries/{}", app.address, id).parse().unwrap();
+        let response = hyper::Client::new().get(uri).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let entry: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(entry["title"], "Served over http");
+    }
+}
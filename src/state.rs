@@ -0,0 +1,43 @@
+//! What the handlers share: the database pool, the configuration and the change feeds.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use metrics_exporter_prometheus::PrometheusHandle;
+use sqlx::postgres::PgPool;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::models::{BlogEntry, ReindexJob};
+use crate::routes::CHANGE_FEED_CAPACITY;
+
+/// Shared dependencies of the handlers, passed as an `Extension` because axum 0.5 has no `State` extractor yet.
+#[derive(Clone)]
+pub struct AppState {
+    pub pool: PgPool,
+    /// For handlers that only read, on a replica when `DATABASE_READ_URL` is set and otherwise the same as `pool`.
+    pub read_pool: PgPool,
+    pub config: Arc<Config>,
+    pub metrics: PrometheusHandle,
+    /// Ids of entries changed through any instance, see `listen_for_changes`.
+    pub changes: broadcast::Sender<Uuid>,
+    /// Entries created through this instance only.
+    pub created: broadcast::Sender<BlogEntry>,
+    /// Search index maintenance started on this instance, by id.
+    pub reindex_jobs: Arc<Mutex<HashMap<Uuid, ReindexJob>>>,
+    /// When the service started, the state is made once at startup.
+    pub started: Instant,
+}
+
+impl AppState {
+    pub fn new(pool: PgPool, config: Config, metrics: PrometheusHandle, changes: broadcast::Sender<Uuid>) -> AppState {
+        let (created, _) = broadcast::channel(CHANGE_FEED_CAPACITY);
+        AppState { read_pool: pool.clone(), pool, config: Arc::new(config), metrics, changes, created, reindex_jobs: Default::default(), started: Instant::now() }
+    }
+
+    /// Sends the reads to `read_pool` instead of the primary pool.
+    pub fn with_read_pool(self, read_pool: PgPool) -> AppState {
+        AppState { read_pool, ..self }
+    }
+}